@@ -0,0 +1,159 @@
+//! Derive macros for the [cohere-rust](https://docs.rs/cohere-rust) crate, enabled by its `derive` feature.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Expr, Fields, Lit, LitStr, Meta};
+
+/// Implements `cohere_rust::agent::CohereTool` for a struct of tool parameters.
+///
+/// The tool is named after the struct in snake case and described by its doc comment. Each field is a parameter
+/// described by its doc comment, whose type is given by `cohere_rust::agent::ParameterType` and which is
+/// required unless it is an `Option`.
+///
+/// The name of the tool can be set with `#[cohere_tool(name = "...")]` on the struct, and the name of a parameter
+/// with `#[cohere_tool(rename = "...")]` on its field.
+#[proc_macro_derive(CohereTool, attributes(cohere_tool))]
+pub fn derive_cohere_tool(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input,
+                    "CohereTool can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "CohereTool can only be derived for structs",
+            ))
+        }
+    };
+
+    let name = attribute(&input.attrs, "name")?
+        .map(|name| name.value())
+        .unwrap_or_else(|| snake_case(&input.ident.to_string()));
+    let description = doc_comment(&input.attrs).ok_or_else(|| {
+        syn::Error::new_spanned(
+            &input.ident,
+            "the tool must have a doc comment, which describes it to the model",
+        )
+    })?;
+
+    let mut definitions = Vec::new();
+    let mut parsers = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().expect("fields are named");
+        let ty = &field.ty;
+        let parameter = attribute(&field.attrs, "rename")?
+            .map(|name| name.value())
+            .unwrap_or_else(|| ident.to_string().trim_start_matches("r#").to_string());
+        let description = match doc_comment(&field.attrs) {
+            Some(description) => quote!(::std::option::Option::Some(#description.to_string())),
+            None => quote!(::std::option::Option::None),
+        };
+
+        definitions.push(quote! {
+            parameter_definitions.insert(
+                #parameter.to_string(),
+                ::cohere_rust::api::chat::ToolParameter {
+                    description: #description,
+                    parameter_type: <#ty as ::cohere_rust::agent::ParameterType>::parameter_type(),
+                    required: <#ty as ::cohere_rust::agent::ParameterType>::missing().is_none(),
+                },
+            );
+        });
+        parsers.push(quote! {
+            #ident: ::cohere_rust::agent::take_parameter(&mut parameters, #parameter)?,
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::cohere_rust::agent::CohereTool for #ident #ty_generics #where_clause {
+            fn definition() -> ::cohere_rust::api::chat::Tool {
+                let mut parameter_definitions = ::std::collections::BTreeMap::new();
+                #(#definitions)*
+                ::cohere_rust::api::chat::Tool {
+                    name: #name.to_string(),
+                    description: #description.to_string(),
+                    parameter_definitions,
+                }
+            }
+
+            fn from_parameters(
+                mut parameters: ::cohere_rust::agent::ToolParameters,
+            ) -> ::std::result::Result<Self, ::cohere_rust::agent::ToolParameterError> {
+                ::std::result::Result::Ok(#ident { #(#parsers)* })
+            }
+        }
+    })
+}
+
+/// Reads the value of `#[cohere_tool(key = "...")]`, rejecting any other keys.
+fn attribute(attrs: &[Attribute], key: &str) -> syn::Result<Option<LitStr>> {
+    let mut value = None;
+    for attr in attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cohere_tool"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) {
+                value = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error(format!("expected `{key}`")))
+            }
+        })?;
+    }
+    Ok(value)
+}
+
+/// Joins the lines of the doc comments into a single line, or returns `None` if there are none.
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(doc) => Some(doc.value().trim().to_string()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect();
+    (!lines.is_empty()).then(|| lines.join(" "))
+}
+
+/// Converts a type name such as `HTTPWeatherLookup` to `http_weather_lookup`.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (index, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            let previous = chars[index - 1];
+            let next_is_lower = chars.get(index + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_is_lower)
+            {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
@@ -7,21 +7,17 @@ async fn main() {
     let co = Cohere::default();
 
     let chat_history = vec![
-        ChatMessage::User {
-            message: "Hello! Tell me about Cohere.".to_string(),
-        },
-        ChatMessage::Chatbot {
-            message: "Cohere is a startup based in Toronto.".to_string(),
-        },
+        ChatMessage::user("Hello! Tell me about Cohere."),
+        ChatMessage::chatbot("Cohere is a startup based in Toronto."),
     ];
 
-    let request = ChatRequest {
-        message: "Tell me more.",
-        preamble: Some("Reply as if you were a Shakespearean actor".to_string()),
-        model: Some(GenerateModel::CommandR082024),
-        chat_history: Some(&chat_history),
-        ..Default::default()
-    };
+    let request = ChatRequest::builder()
+        .message("Tell me more.")
+        .preamble("Reply as if you were a Shakespearean actor")
+        .model(GenerateModel::CommandR082024)
+        .chat_history(chat_history)
+        .build()
+        .expect("chat request should be valid");
 
     match co.chat(&request).await {
         Ok(mut rx) => {
@@ -8,44 +8,44 @@ async fn main() {
 
     let examples = &vec![
         ClassifyExample {
-            text: "Dermatologists don't like her!",
-            label: "Spam",
+            text: "Dermatologists don't like her!".into(),
+            label: "Spam".into(),
         },
         ClassifyExample {
-            text: "Hello, open to this?",
-            label: "Spam",
+            text: "Hello, open to this?".into(),
+            label: "Spam".into(),
         },
         ClassifyExample {
-            text: "I need help please wire me $1000 right now",
-            label: "Spam",
+            text: "I need help please wire me $1000 right now".into(),
+            label: "Spam".into(),
         },
         ClassifyExample {
-            text: "Nice to know you ;)",
-            label: "Spam",
+            text: "Nice to know you ;)".into(),
+            label: "Spam".into(),
         },
         ClassifyExample {
-            text: "Please help me?",
-            label: "Spam",
+            text: "Please help me?".into(),
+            label: "Spam".into(),
         },
         ClassifyExample {
-            text: "Your parcel will be delivered today",
-            label: "Not spam",
+            text: "Your parcel will be delivered today".into(),
+            label: "Not spam".into(),
         },
         ClassifyExample {
-            text: "Review changes to our Terms and Conditions",
-            label: "Not spam",
+            text: "Review changes to our Terms and Conditions".into(),
+            label: "Not spam".into(),
         },
         ClassifyExample {
-            text: "Weekly sync notes",
-            label: "Not spam",
+            text: "Weekly sync notes".into(),
+            label: "Not spam".into(),
         },
         ClassifyExample {
-            text: "Re: Follow up from today's meeting",
-            label: "Not spam",
+            text: "Re: Follow up from today's meeting".into(),
+            label: "Not spam".into(),
         },
         ClassifyExample {
-            text: "Pre-read for tomorrow",
-            label: "Not spam",
+            text: "Pre-read for tomorrow".into(),
+            label: "Not spam".into(),
         },
     ];
 
@@ -55,8 +55,8 @@ async fn main() {
     ];
 
     let request = ClassifyRequest {
-        examples,
-        inputs,
+        examples: examples.into(),
+        inputs: inputs.into(),
         model: Some(EmbedModel::EnglishLightV3),
         ..Default::default()
     };
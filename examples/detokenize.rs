@@ -5,7 +5,7 @@ async fn main() {
     let co = Cohere::default();
 
     let request = DetokenizeRequest {
-        tokens: &[10002, 2261, 2012, 8, 2792, 43],
+        tokens: vec![10002, 2261, 2012, 8, 2792, 43].into(),
         model: Some(GenerateModel::Command),
     };
 
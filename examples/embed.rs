@@ -8,7 +8,7 @@ async fn main() {
 
     let request = EmbedRequest {
         model: Some(EmbedModel::EnglishLightV2),
-        texts: &["hello".to_string(), "goodbye".to_string()],
+        texts: vec!["hello".to_string(), "goodbye".to_string()].into(),
         truncate: Truncate::End,
     };
 
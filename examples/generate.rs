@@ -13,7 +13,7 @@ async fn main() {
         return_likelihoods: Some(ReturnLikelihoods::None),
         truncate: Some(Truncate::End),
         model: Some(cohere_rust::api::GenerateModel::CommandR082024),
-        prompt: "Once upon a time in a magical land called",
+        prompt: "Once upon a time in a magical land called".into(),
         ..Default::default()
     };
 
@@ -0,0 +1,29 @@
+use cohere_rust::api::generate::GenerateRequest;
+use cohere_rust::api::GenerateModel;
+use cohere_rust::Cohere;
+
+#[tokio::main]
+async fn main() {
+    let co = Cohere::default();
+
+    let request = GenerateRequest {
+        max_tokens: Some(50),
+        model: Some(GenerateModel::CommandR082024),
+        prompt: "Once upon a time in a magical land called".into(),
+        ..Default::default()
+    };
+
+    match co.generate_stream(&request).await {
+        Ok(mut rx) => {
+            while let Some(message) = rx.recv().await {
+                match message {
+                    Ok(message) => println!("Generate response: {:#?}", message),
+                    Err(e) => println!("Generate error! {:#?}", e),
+                }
+            }
+        }
+        Err(e) => {
+            println!("Generate failed! {}", e)
+        }
+    }
+}
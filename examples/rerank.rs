@@ -1,5 +1,5 @@
 use cohere_rust::{
-    api::rerank::{ReRankModel, ReRankRequest},
+    api::rerank::{ReRankDocument, ReRankModel, ReRankRequest},
     Cohere,
 };
 
@@ -15,8 +15,8 @@ async fn main() {
     ];
 
     let request = ReRankRequest {
-        query: "What is the capital of the United States?",
-        documents: &documents.map(|d| d.to_string()),
+        query: "What is the capital of the United States?".into(),
+        documents: documents.map(ReRankDocument::from).to_vec().into(),
         model: ReRankModel::EnglishV3,
         top_n: Some(2),
         ..Default::default()
@@ -5,7 +5,7 @@ async fn main() {
     let co = Cohere::default();
 
     let request = TokenizeRequest {
-        text: "tokenize me! :D",
+        text: "tokenize me! :D".into(),
         model: Some(GenerateModel::Command),
     };
 
@@ -0,0 +1,475 @@
+//! An agent that answers messages by calling tools until the model has what it needs to reply.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    future::{poll_fn, Future},
+    marker::PhantomData,
+    pin::Pin,
+    task::Poll,
+};
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+use crate::{
+    api::chat::{ChatRequest, ChatResponse, ChatStreamResponse, Tool, ToolCall, ToolResult},
+    session::{ChatSession, ChatTurnStream},
+    CohereApi, CohereApiError,
+};
+
+/// The number of tool-calling steps after which a run is stopped, unless set with [`ToolAgent::max_steps`].
+const DEFAULT_MAX_STEPS: usize = 10;
+
+/// The error returned by a tool, which is passed to the model as the output of the call.
+pub type ToolError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Error, Debug)]
+pub enum AgentError {
+    #[error("Chat request failed")]
+    ApiError(#[from] CohereApiError),
+    #[error("The model was still calling tools after {} steps", .0.len())]
+    StepLimitReached(Vec<AgentStep>),
+}
+
+/// A tool that can be called by a [`ToolAgent`].
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait AgentTool: Send + Sync {
+    /// The name, description and parameters of the tool as they are sent to the model.
+    fn definition(&self) -> Tool;
+
+    /// Runs the tool with the parameters of a call, returning its outputs, each of which must be a JSON object.
+    async fn call(&self, parameters: Map<String, Value>) -> Result<Vec<Value>, ToolError>;
+}
+
+/// A tool implemented by an async closure.
+///
+/// ```
+/// # use cohere_rust::{agent::{FnTool, ToolError}, api::chat::Tool};
+/// # use serde_json::{json, Map, Value};
+/// let weather = FnTool::new(
+///     Tool {
+///         name: "weather".to_string(),
+///         description: "Looks up the weather in a city".to_string(),
+///         parameter_definitions: Default::default(),
+///     },
+///     |parameters: Map<String, Value>| async move {
+///         Ok::<_, ToolError>(vec![json!({ "city": parameters["city"], "forecast": "sunny" })])
+///     },
+/// );
+/// ```
+pub struct FnTool<F> {
+    definition: Tool,
+    function: F,
+}
+
+impl<F, Fut> FnTool<F>
+where
+    F: Fn(Map<String, Value>) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Vec<Value>, ToolError>> + Send,
+{
+    pub fn new(definition: Tool, function: F) -> Self {
+        FnTool {
+            definition,
+            function,
+        }
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl<F, Fut> AgentTool for FnTool<F>
+where
+    F: Fn(Map<String, Value>) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Vec<Value>, ToolError>> + Send,
+{
+    fn definition(&self) -> Tool {
+        self.definition.clone()
+    }
+
+    async fn call(&self, parameters: Map<String, Value>) -> Result<Vec<Value>, ToolError> {
+        (self.function)(parameters).await
+    }
+}
+
+/// The parameters of a tool call as they are sent by the model.
+pub type ToolParameters = Map<String, Value>;
+
+#[derive(Error, Debug)]
+pub enum ToolParameterError {
+    #[error("Missing required parameter `{0}`")]
+    Missing(&'static str),
+    #[error("Invalid value for parameter `{0}`: {1}")]
+    Invalid(&'static str, serde_json::Error),
+}
+
+/// The parameters of a tool, from which the tool's definition is generated and into which calls are parsed.
+///
+/// With the `derive` feature, this is implemented by `#[derive(CohereTool)]`, which takes the tool's description
+/// and the descriptions of its parameters from the doc comments:
+///
+/// ```ignore
+/// use cohere_rust::agent::CohereTool;
+///
+/// /// Looks up the weather in a city.
+/// #[derive(CohereTool)]
+/// struct Weather {
+///     /// The name of the city.
+///     city: String,
+///     /// The number of days to forecast, defaults to 1.
+///     days: Option<u8>,
+/// }
+/// ```
+pub trait CohereTool: Sized {
+    /// The name, description and parameters of the tool as they are sent to the model.
+    fn definition() -> Tool;
+
+    /// Parses the parameters of a call to the tool.
+    fn from_parameters(parameters: ToolParameters) -> Result<Self, ToolParameterError>;
+}
+
+#[cfg(feature = "derive")]
+pub use cohere_rust_derive::CohereTool;
+
+/// A type that can be used as a tool parameter, described to the model by a Python type such as `str` or `List[int]`.
+pub trait ParameterType: Sized {
+    fn parameter_type() -> String;
+
+    /// The value of the parameter when the model leaves it out, or `None` if the parameter is required.
+    fn missing() -> Option<Self> {
+        None
+    }
+}
+
+macro_rules! parameter_types {
+    ($name:literal: $($ty:ty),*) => {
+        $(
+            impl ParameterType for $ty {
+                fn parameter_type() -> String {
+                    $name.to_string()
+                }
+            }
+        )*
+    };
+}
+
+parameter_types!("str": String);
+parameter_types!("int": i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+parameter_types!("float": f32, f64);
+parameter_types!("bool": bool);
+
+impl<T: ParameterType> ParameterType for Option<T> {
+    fn parameter_type() -> String {
+        T::parameter_type()
+    }
+
+    fn missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: ParameterType> ParameterType for Vec<T> {
+    fn parameter_type() -> String {
+        format!("List[{}]", T::parameter_type())
+    }
+}
+
+impl<V: ParameterType> ParameterType for BTreeMap<String, V> {
+    fn parameter_type() -> String {
+        format!("Dict[str, {}]", V::parameter_type())
+    }
+}
+
+impl<V: ParameterType> ParameterType for HashMap<String, V> {
+    fn parameter_type() -> String {
+        format!("Dict[str, {}]", V::parameter_type())
+    }
+}
+
+/// Removes a parameter from the parameters of a call and parses it, for `#[derive(CohereTool)]`.
+#[doc(hidden)]
+pub fn take_parameter<T: ParameterType + DeserializeOwned>(
+    parameters: &mut ToolParameters,
+    name: &'static str,
+) -> Result<T, ToolParameterError> {
+    match parameters.remove(name) {
+        Some(value) => {
+            serde_json::from_value(value).map_err(|error| ToolParameterError::Invalid(name, error))
+        }
+        None => T::missing().ok_or(ToolParameterError::Missing(name)),
+    }
+}
+
+/// A tool implemented by an async closure that takes its parameters as a [`CohereTool`], which defines the tool.
+pub struct TypedTool<P, F> {
+    function: F,
+    parameters: PhantomData<fn() -> P>,
+}
+
+impl<P, F, Fut> TypedTool<P, F>
+where
+    P: CohereTool,
+    F: Fn(P) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Vec<Value>, ToolError>> + Send,
+{
+    pub fn new(function: F) -> Self {
+        TypedTool {
+            function,
+            parameters: PhantomData,
+        }
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl<P, F, Fut> AgentTool for TypedTool<P, F>
+where
+    P: CohereTool,
+    F: Fn(P) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Vec<Value>, ToolError>> + Send,
+{
+    fn definition(&self) -> Tool {
+        P::definition()
+    }
+
+    async fn call(&self, parameters: Map<String, Value>) -> Result<Vec<Value>, ToolError> {
+        let parameters = P::from_parameters(parameters)?;
+        (self.function)(parameters).await
+    }
+}
+
+/// A step of a run in which the model called tools instead of replying.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AgentStep {
+    /// The text the model generated while planning the tool calls, if any.
+    pub text: String,
+    /// The calls requested by the model.
+    pub tool_calls: Vec<ToolCall>,
+    /// The results sent back to the model, in the order of the calls.
+    pub tool_results: Vec<ToolResult>,
+}
+
+/// The steps and final reply of a run.
+#[derive(Debug, PartialEq)]
+pub struct AgentRun {
+    pub steps: Vec<AgentStep>,
+    pub response: ChatResponse,
+}
+
+/// What happens during a run, as reported to the callback of [`ToolAgent::run_with`].
+#[derive(Debug)]
+pub enum AgentEvent<'run> {
+    /// A message streamed by the chat endpoint, including the text of the final reply as it is generated.
+    Message(&'run ChatStreamResponse),
+    /// A step in which the model called tools, reported once the tools have returned.
+    Step(&'run AgentStep),
+}
+
+/// Answers messages with the chat endpoint, calling the tools requested by the model and sending their results
+/// back until the model replies.
+///
+/// The tools requested in a step are called concurrently. A tool that fails or isn't known is reported to the model
+/// as an output with an `error` field, so that it can try something else. The conversation is kept in
+/// [`ToolAgent::session`], so later messages can refer to earlier ones.
+///
+/// ```no_run
+/// # use cohere_rust::{agent::{FnTool, ToolAgent, ToolError}, api::chat::{ChatRequest, Tool}, Cohere};
+/// # use serde_json::{json, Map, Value};
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Cohere::default();
+/// let mut agent = ToolAgent::new(ChatRequest::default()).tool(FnTool::new(
+///     Tool {
+///         name: "weather".to_string(),
+///         description: "Looks up the weather in a city".to_string(),
+///         parameter_definitions: Default::default(),
+///     },
+///     |_: Map<String, Value>| async { Ok::<_, ToolError>(vec![json!({ "forecast": "sunny" })]) },
+/// ));
+///
+/// let run = agent.run(&client, "What's the weather in Toronto?").await?;
+/// for step in &run.steps {
+///     println!("called {:?}", step.tool_calls);
+/// }
+/// println!("{}", run.response.text);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ToolAgent {
+    /// The conversation, whose settings are sent with every step along with the definitions of the tools.
+    pub session: ChatSession,
+    tools: BTreeMap<String, Box<dyn AgentTool>>,
+    max_steps: usize,
+}
+
+impl ToolAgent {
+    pub fn new(settings: ChatRequest<'static>) -> Self {
+        ToolAgent {
+            session: ChatSession::new(settings),
+            tools: BTreeMap::new(),
+            max_steps: DEFAULT_MAX_STEPS,
+        }
+    }
+
+    /// Adds a tool the model can call, replacing any tool with the same name.
+    pub fn tool<T: AgentTool + 'static>(mut self, tool: T) -> Self {
+        let definition = tool.definition();
+        let definitions = self
+            .session
+            .settings
+            .tools
+            .get_or_insert_with(Default::default)
+            .to_mut();
+        definitions.retain(|existing| existing.name != definition.name);
+        self.tools.insert(definition.name.clone(), Box::new(tool));
+        definitions.push(definition);
+        self
+    }
+
+    /// Sets the number of steps in which the model can call tools before a run fails. Defaults to 10.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Answers a message, calling tools as requested by the model.
+    pub async fn run<C: CohereApi + ?Sized>(
+        &mut self,
+        client: &C,
+        message: impl Into<String>,
+    ) -> Result<AgentRun, AgentError> {
+        self.run_with(client, message, |_| {}).await
+    }
+
+    /// Answers a message like [`ToolAgent::run`], passing every streamed message and step to the callback as it
+    /// happens, for example to log the steps and print the final reply as it is generated.
+    ///
+    /// A run that fails isn't added to the history, so it can be retried.
+    pub async fn run_with<C: CohereApi + ?Sized>(
+        &mut self,
+        client: &C,
+        message: impl Into<String>,
+        mut on_event: impl FnMut(AgentEvent<'_>),
+    ) -> Result<AgentRun, AgentError> {
+        let history = self.session.history.clone();
+        let mut steps = Vec::new();
+        let result = self
+            .run_steps(client, message.into(), &mut steps, &mut on_event)
+            .await;
+
+        if result.is_err() {
+            self.session.history = history;
+        }
+        match result {
+            Ok(response) => Ok(AgentRun { steps, response }),
+            Err(AgentError::StepLimitReached(_)) => Err(AgentError::StepLimitReached(steps)),
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn run_steps<C: CohereApi + ?Sized>(
+        &mut self,
+        client: &C,
+        message: String,
+        steps: &mut Vec<AgentStep>,
+        on_event: &mut impl FnMut(AgentEvent<'_>),
+    ) -> Result<ChatResponse, AgentError> {
+        let stream = self.session.send_stream(client, message).await?;
+        let mut response = receive(stream, on_event).await?;
+
+        while !response.tool_calls.is_empty() {
+            if steps.len() == self.max_steps {
+                return Err(AgentError::StepLimitReached(Vec::new()));
+            }
+
+            let calls = response
+                .tool_calls
+                .iter()
+                .map(|call| call_tool(&self.tools, call));
+            let tool_results = join_all(calls.collect()).await;
+            let step = AgentStep {
+                text: response.text,
+                tool_calls: response.tool_calls,
+                tool_results,
+            };
+            on_event(AgentEvent::Step(&step));
+
+            let stream = self
+                .session
+                .send_tool_results_stream(client, step.tool_results.clone())
+                .await?;
+            steps.push(step);
+            response = receive(stream, on_event).await?;
+        }
+        Ok(response)
+    }
+}
+
+impl fmt::Debug for ToolAgent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolAgent")
+            .field("session", &self.session)
+            .field("tools", &self.tools.keys().collect::<Vec<_>>())
+            .field("max_steps", &self.max_steps)
+            .finish()
+    }
+}
+
+/// Reads a streamed reply, passing each message to the callback, and returns the complete response.
+async fn receive(
+    mut stream: ChatTurnStream<'_>,
+    on_event: &mut impl FnMut(AgentEvent<'_>),
+) -> Result<ChatResponse, CohereApiError> {
+    let mut response = None;
+    while let Some(message) = stream.recv().await {
+        let message = message?;
+        on_event(AgentEvent::Message(&message));
+        if let ChatStreamResponse::ChatStreamEnd { response: end, .. } = message {
+            response = Some(end);
+        }
+    }
+    response.ok_or_else(|| {
+        CohereApiError::TransportError("the chat stream ended before the reply was complete".into())
+    })
+}
+
+async fn call_tool(tools: &BTreeMap<String, Box<dyn AgentTool>>, call: &ToolCall) -> ToolResult {
+    let result = match tools.get(&call.name) {
+        Some(tool) => tool.call(call.parameters.clone()).await,
+        None => Err(format!("there is no tool named `{}`", call.name).into()),
+    };
+    ToolResult {
+        call: call.clone(),
+        outputs: result.unwrap_or_else(|error| vec![json!({ "error": error.to_string() })]),
+    }
+}
+
+/// Runs the futures concurrently and returns their outputs in order.
+async fn join_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+    let mut futures: Vec<_> = futures.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    poll_fn(|cx| {
+        let mut pending = false;
+        for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if output.is_none() {
+                match Pin::as_mut(future).poll(cx) {
+                    Poll::Ready(value) => *output = Some(value),
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    })
+    .await;
+    outputs
+        .into_iter()
+        .map(|output| output.expect("every future has completed"))
+        .collect()
+}
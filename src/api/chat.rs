@@ -1,11 +1,18 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
-use super::GenerateModel;
+use super::{
+    optional_setters, validate_p, validate_penalty, validate_temperature, FinishReason,
+    GenerateModel,
+};
+use crate::CohereRequestError;
 
-#[derive(Serialize, Default, Debug)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct ChatRequest<'input> {
     /// The chat message from the user to the model.
-    pub message: &'input str,
+    pub message: Cow<'input, str>,
     /// optional - The model to use for text generation. Custom models can also be supplied with their full ID. Defaults to 'command'.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<GenerateModel>,
@@ -23,7 +30,7 @@ pub struct ChatRequest<'input> {
     /// optional - A list of previous messages between the user and the model,
     /// meant to give the model conversational context for responding to the user's message.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub chat_history: Option<&'input Vec<ChatMessage>>,
+    pub chat_history: Option<Cow<'input, [ChatMessage]>>,
     // optional - When specified, the default Cohere preamble will be replaced with the provided one.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub preamble: Option<String>,
@@ -33,15 +40,219 @@ pub struct ChatRequest<'input> {
     // optional - Ensures only the top k most likely tokens are considered for generation at each step
     #[serde(skip_serializing_if = "Option::is_none")]
     pub k: Option<u64>,
+    /// optional - Ensures that only the most likely tokens, with total probability mass of p, are considered
+    /// for generation at each step. If both k and p are enabled, p acts after k. Defaults to 0.75.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p: Option<f64>,
+    /// optional - If specified, the backend will make a best effort to sample tokens deterministically,
+    /// such that repeated requests with the same seed and parameters should return the same result.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// optional - A list of up to 5 strings that the model will use to stop generation.
+    /// The sequence will be excluded from the text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    /// optional - Used to reduce repetitiveness of generated tokens. The higher the value, the stronger a penalty
+    /// is applied to previously present tokens, proportional to how many times they have already appeared.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f64>,
+    /// optional - Used to reduce repetitiveness of generated tokens. Similar to frequency_penalty, except that
+    /// this penalty is applied equally to all tokens that have already appeared, regardless of their exact frequencies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f64>,
+    /// optional - When enabled, the user's prompt will be sent to the model without any pre-processing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_prompting: Option<bool>,
+    /// optional - When enabled, the full prompt that was sent to the model is returned in the response.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub return_prompt: Option<bool>,
+    /// optional - Used to select the safety instruction inserted into the prompt. Defaults to CONTEXTUAL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_mode: Option<SafetyMode>,
+    /// optional - The maximum number of input tokens to send to the model.
+    /// If not specified, the model's context length limit minus a small buffer is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_input_tokens: Option<u64>,
+    /// optional - Tools the model can call instead of replying. The calls are returned in the response's `tool_calls`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Cow<'input, [Tool]>>,
+    /// optional - The results of the tool calls requested by the model in its previous reply.
+    /// The message should be empty when tool results are sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_results: Option<Cow<'input, [ToolResult]>>,
+    /// optional - When enabled, the model replies to tool results without calling more tools.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force_single_step: Option<bool>,
+}
+
+impl<'input> ChatRequest<'input> {
+    /// Returns a builder that validates the request parameters when built.
+    pub fn builder() -> ChatRequestBuilder<'input> {
+        ChatRequestBuilder::default()
+    }
+
+    /// Converts the request into one that owns all of its data.
+    pub fn into_owned(self) -> ChatRequest<'static> {
+        ChatRequest {
+            message: Cow::Owned(self.message.into_owned()),
+            chat_history: self
+                .chat_history
+                .map(|history| Cow::Owned(history.into_owned())),
+            tools: self.tools.map(|tools| Cow::Owned(tools.into_owned())),
+            tool_results: self
+                .tool_results
+                .map(|results| Cow::Owned(results.into_owned())),
+            ..self
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct ChatRequestBuilder<'input> {
+    message: Option<Cow<'input, str>>,
+    request: ChatRequest<'input>,
+}
+
+impl<'input> ChatRequestBuilder<'input> {
+    /// Sets the chat message from the user to the model.
+    pub fn message(mut self, message: impl Into<Cow<'input, str>>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    optional_setters! {
+        model: GenerateModel,
+        prompt_truncation: PromptTruncation,
+        temperature: f64,
+        conversation_id: String,
+        chat_history: Cow<'input, [ChatMessage]>,
+        preamble: String,
+        max_tokens: u64,
+        k: u64,
+        p: f64,
+        seed: u64,
+        stop_sequences: Vec<String>,
+        frequency_penalty: f64,
+        presence_penalty: f64,
+        raw_prompting: bool,
+        return_prompt: bool,
+        safety_mode: SafetyMode,
+        max_input_tokens: u64,
+        tools: Cow<'input, [Tool]>,
+        tool_results: Cow<'input, [ToolResult]>,
+        force_single_step: bool,
+    }
+
+    /// Validates the parameters and builds the request.
+    pub fn build(self) -> Result<ChatRequest<'input>, CohereRequestError> {
+        let message = self
+            .message
+            .ok_or(CohereRequestError::MissingField("message"))?;
+        validate_temperature(self.request.temperature)?;
+        validate_p(self.request.p)?;
+        validate_penalty("frequency_penalty", self.request.frequency_penalty)?;
+        validate_penalty("presence_penalty", self.request.presence_penalty)?;
+
+        Ok(ChatRequest {
+            message,
+            ..self.request
+        })
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(tag = "role")]
+#[non_exhaustive]
 pub enum ChatMessage {
     #[serde(rename = "CHATBOT")]
-    Chatbot { message: String },
+    Chatbot {
+        message: String,
+        /// The tools the model called instead of replying.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tool_calls: Vec<ToolCall>,
+    },
     #[serde(rename = "USER")]
     User { message: String },
+    #[serde(rename = "SYSTEM")]
+    System { message: String },
+    /// The results of the tools called by the model.
+    #[serde(rename = "TOOL")]
+    Tool { tool_results: Vec<ToolResult> },
+}
+
+impl ChatMessage {
+    /// A reply from the model that didn't call any tools.
+    pub fn chatbot(message: impl Into<String>) -> Self {
+        ChatMessage::Chatbot {
+            message: message.into(),
+            tool_calls: Vec::new(),
+        }
+    }
+
+    /// A message from the user.
+    pub fn user(message: impl Into<String>) -> Self {
+        ChatMessage::User {
+            message: message.into(),
+        }
+    }
+
+    /// A system message, such as instructions for the rest of the conversation.
+    pub fn system(message: impl Into<String>) -> Self {
+        ChatMessage::System {
+            message: message.into(),
+        }
+    }
+}
+
+/// A tool the model can call, described by its name, what it does and its parameters.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Tool {
+    pub name: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameter_definitions: BTreeMap<String, ToolParameter>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ToolParameter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The type of the parameter as a Python type, for example `str`, `int` or `List[str]`.
+    #[serde(rename = "type")]
+    pub parameter_type: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// A call to a tool requested by the model.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ToolCall {
+    pub name: String,
+    #[serde(default)]
+    pub parameters: Map<String, Value>,
+}
+
+/// The outputs of a tool call, which are sent back to the model.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ToolResult {
+    pub call: ToolCall,
+    /// The outputs of the tool, each of which must be a JSON object.
+    pub outputs: Vec<Value>,
+}
+
+/// A part of a tool call streamed as the model generates it.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ToolCallDelta {
+    #[serde(default)]
+    pub index: Option<u64>,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// A part of the JSON object of parameters.
+    #[serde(default)]
+    pub parameters: Option<String>,
+    /// A part of the text the model generates while planning the tool calls.
+    #[serde(default)]
+    pub text: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
@@ -51,7 +262,7 @@ pub(crate) struct ChatStreamRequest<'input> {
     pub stream: bool,
 }
 
-#[derive(strum_macros::Display, Serialize, Debug)]
+#[derive(strum_macros::Display, Serialize, Deserialize, Clone, Debug)]
 pub enum PromptTruncation {
     #[strum(serialize = "AUTO")]
     #[serde(rename = "AUTO")]
@@ -61,7 +272,7 @@ pub enum PromptTruncation {
     Off,
 }
 
-#[derive(strum_macros::Display, Serialize, Debug)]
+#[derive(strum_macros::Display, Serialize, Deserialize, Clone, Debug)]
 pub enum CitationQuality {
     #[strum(serialize = "accurate")]
     #[serde(rename = "accurate")]
@@ -71,15 +282,35 @@ pub enum CitationQuality {
     Fast,
 }
 
+#[derive(strum_macros::Display, Serialize, Deserialize, Clone, Debug)]
+pub enum SafetyMode {
+    #[strum(serialize = "CONTEXTUAL")]
+    #[serde(rename = "CONTEXTUAL")]
+    Contextual,
+    #[strum(serialize = "STRICT")]
+    #[serde(rename = "STRICT")]
+    Strict,
+    #[strum(serialize = "NONE")]
+    #[serde(rename = "NONE")]
+    None,
+}
+
 #[derive(Deserialize, Debug, PartialEq)]
 pub struct ChatResponse {
     pub generation_id: String,
     pub response_id: String,
     pub text: String,
+    /// The prompt that was used, only returned when `return_prompt` is enabled.
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// The tools the model called instead of replying, if any tools were provided.
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
 #[serde(tag = "event_type")]
+#[non_exhaustive]
 pub enum ChatStreamResponse {
     #[serde(rename = "stream-start")]
     ChatStreamStart {
@@ -88,9 +319,22 @@ pub enum ChatStreamResponse {
     },
     #[serde(rename = "text-generation")]
     ChatTextGeneration { is_finished: bool, text: String },
+    #[serde(rename = "tool-calls-chunk")]
+    ChatToolCallsChunk {
+        is_finished: bool,
+        tool_call_delta: ToolCallDelta,
+    },
+    #[serde(rename = "tool-calls-generation")]
+    ChatToolCallsGeneration {
+        is_finished: bool,
+        /// The text the model generated while planning the tool calls.
+        #[serde(default)]
+        text: Option<String>,
+        tool_calls: Vec<ToolCall>,
+    },
     #[serde(rename = "stream-end")]
     ChatStreamEnd {
-        finish_reason: String,
+        finish_reason: FinishReason,
         is_finished: bool,
         response: ChatResponse,
     },
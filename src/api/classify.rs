@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap};
 
 use serde::{Deserialize, Serialize};
 
-use super::{EmbedModel, Truncate};
+use super::{optional_setters, EmbedModel, Truncate};
+use crate::{
+    telemetry::{ResponseMeta, ResponseUsage},
+    CohereRequestError,
+};
 
-#[derive(Serialize, Default, Debug)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct ClassifyRequest<'input> {
     /// An optional string representing the model you'd like to use.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -13,19 +17,94 @@ pub struct ClassifyRequest<'input> {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub preset: Option<String>,
     /// An array of strings that you would like to classify.
-    pub inputs: &'input [String],
+    pub inputs: Cow<'input, [String]>,
     /// An array of ClassifyExamples representing examples and the corresponding label.
-    pub examples: &'input [ClassifyExample<'input>],
+    pub examples: Cow<'input, [ClassifyExample<'input>]>,
     /// Specify how the API will handle inputs longer than the maximum token length.
     pub truncate: Option<Truncate>,
 }
 
-#[derive(Serialize, Debug)]
+impl<'input> ClassifyRequest<'input> {
+    /// Returns a builder that validates the request parameters when built.
+    pub fn builder() -> ClassifyRequestBuilder<'input> {
+        ClassifyRequestBuilder::default()
+    }
+
+    /// Converts the request into one that owns all of its data.
+    pub fn into_owned(self) -> ClassifyRequest<'static> {
+        ClassifyRequest {
+            inputs: Cow::Owned(self.inputs.into_owned()),
+            examples: Cow::Owned(
+                self.examples
+                    .iter()
+                    .cloned()
+                    .map(ClassifyExample::into_owned)
+                    .collect(),
+            ),
+            ..self
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct ClassifyRequestBuilder<'input> {
+    inputs: Option<Cow<'input, [String]>>,
+    examples: Option<Cow<'input, [ClassifyExample<'input>]>>,
+    request: ClassifyRequest<'input>,
+}
+
+impl<'input> ClassifyRequestBuilder<'input> {
+    /// Sets the strings to classify.
+    pub fn inputs(mut self, inputs: impl Into<Cow<'input, [String]>>) -> Self {
+        self.inputs = Some(inputs.into());
+        self
+    }
+
+    /// Sets the examples and their corresponding labels.
+    pub fn examples(mut self, examples: impl Into<Cow<'input, [ClassifyExample<'input>]>>) -> Self {
+        self.examples = Some(examples.into());
+        self
+    }
+
+    optional_setters! {
+        model: EmbedModel,
+        preset: String,
+        truncate: Truncate,
+    }
+
+    /// Validates the parameters and builds the request.
+    pub fn build(self) -> Result<ClassifyRequest<'input>, CohereRequestError> {
+        let inputs = self
+            .inputs
+            .ok_or(CohereRequestError::MissingField("inputs"))?;
+        let examples = self
+            .examples
+            .ok_or(CohereRequestError::MissingField("examples"))?;
+
+        Ok(ClassifyRequest {
+            inputs,
+            examples,
+            ..self.request
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ClassifyExample<'input> {
     /// The text of the example.
-    pub text: &'input str,
+    pub text: Cow<'input, str>,
     /// The label that fits the example's text.
-    pub label: &'input str,
+    pub label: Cow<'input, str>,
+}
+
+impl ClassifyExample<'_> {
+    /// Converts the example into one that owns all of its data.
+    pub fn into_owned(self) -> ClassifyExample<'static> {
+        ClassifyExample {
+            text: Cow::Owned(self.text.into_owned()),
+            label: Cow::Owned(self.label.into_owned()),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -57,4 +136,18 @@ pub struct Classification {
 #[derive(Deserialize, Debug)]
 pub(crate) struct ClassifyResponse {
     pub classifications: Vec<Classification>,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub meta: Option<ResponseMeta>,
+}
+
+impl ResponseUsage for ClassifyResponse {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn meta(&self) -> Option<&ResponseMeta> {
+        self.meta.as_ref()
+    }
 }
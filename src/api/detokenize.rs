@@ -1,18 +1,68 @@
+use std::borrow::Cow;
+
 use serde::{Deserialize, Serialize};
 
-use super::GenerateModel;
+use super::{optional_setters, GenerateModel};
+use crate::{telemetry::ResponseUsage, CohereRequestError};
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct DetokenizeRequest<'input> {
     /// The tokens to be detokenized
-    pub tokens: &'input [u64],
+    pub tokens: Cow<'input, [u64]>,
     /// optional - The model to use for detokenization. Custom models can also be supplied with their full ID.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<GenerateModel>,
 }
 
+impl<'input> DetokenizeRequest<'input> {
+    /// Returns a builder that validates the request parameters when built.
+    pub fn builder() -> DetokenizeRequestBuilder<'input> {
+        DetokenizeRequestBuilder::default()
+    }
+
+    /// Converts the request into one that owns all of its data.
+    pub fn into_owned(self) -> DetokenizeRequest<'static> {
+        DetokenizeRequest {
+            tokens: Cow::Owned(self.tokens.into_owned()),
+            ..self
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct DetokenizeRequestBuilder<'input> {
+    tokens: Option<Cow<'input, [u64]>>,
+    request: DetokenizeRequest<'input>,
+}
+
+impl<'input> DetokenizeRequestBuilder<'input> {
+    /// Sets the tokens to be detokenized.
+    pub fn tokens(mut self, tokens: impl Into<Cow<'input, [u64]>>) -> Self {
+        self.tokens = Some(tokens.into());
+        self
+    }
+
+    optional_setters! {
+        model: GenerateModel,
+    }
+
+    /// Validates the parameters and builds the request.
+    pub fn build(self) -> Result<DetokenizeRequest<'input>, CohereRequestError> {
+        let tokens = self
+            .tokens
+            .ok_or(CohereRequestError::MissingField("tokens"))?;
+
+        Ok(DetokenizeRequest {
+            tokens,
+            ..self.request
+        })
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct DetokenizeResponse {
     /// The text representation of the tokens
     pub text: String,
 }
+
+impl ResponseUsage for DetokenizeResponse {}
@@ -1,21 +1,92 @@
+use std::borrow::Cow;
+
 use serde::{Deserialize, Serialize};
 
-use super::{EmbedModel, Truncate};
+use super::{optional_setters, EmbedModel, Truncate};
+use crate::{
+    telemetry::{ResponseMeta, ResponseUsage},
+    CohereRequestError,
+};
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct EmbedRequest<'input> {
     /// An optional string representing the model you'd like to use.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<EmbedModel>,
     /// An array of strings for the model to embed.
-    pub texts: &'input [String],
+    pub texts: Cow<'input, [String]>,
     /// Specify how the API will handle inputs longer than the maximum token length.
     pub truncate: Truncate,
 }
 
+impl<'input> EmbedRequest<'input> {
+    /// Returns a builder that validates the request parameters when built.
+    pub fn builder() -> EmbedRequestBuilder<'input> {
+        EmbedRequestBuilder::default()
+    }
+
+    /// Converts the request into one that owns all of its data.
+    pub fn into_owned(self) -> EmbedRequest<'static> {
+        EmbedRequest {
+            texts: Cow::Owned(self.texts.into_owned()),
+            ..self
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct EmbedRequestBuilder<'input> {
+    texts: Option<Cow<'input, [String]>>,
+    request: EmbedRequest<'input>,
+}
+
+impl<'input> EmbedRequestBuilder<'input> {
+    /// Sets the strings for the model to embed.
+    pub fn texts(mut self, texts: impl Into<Cow<'input, [String]>>) -> Self {
+        self.texts = Some(texts.into());
+        self
+    }
+
+    /// Sets how the API will handle inputs longer than the maximum token length. Defaults to END.
+    pub fn truncate(mut self, truncate: Truncate) -> Self {
+        self.request.truncate = truncate;
+        self
+    }
+
+    optional_setters! {
+        model: EmbedModel,
+    }
+
+    /// Validates the parameters and builds the request.
+    pub fn build(self) -> Result<EmbedRequest<'input>, CohereRequestError> {
+        let texts = self
+            .texts
+            .ok_or(CohereRequestError::MissingField("texts"))?;
+
+        Ok(EmbedRequest {
+            texts,
+            ..self.request
+        })
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct EmbedResponse {
     /// An array of embeddings, where each embedding is an array of floats. The length of the embeddings
     /// array will be the same as the length of the original texts array.
     pub embeddings: Vec<Vec<f64>>,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub meta: Option<ResponseMeta>,
+}
+
+impl ResponseUsage for EmbedResponse {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn meta(&self) -> Option<&ResponseMeta> {
+        self.meta.as_ref()
+    }
 }
@@ -1,12 +1,19 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap};
 
-use super::{GenerateModel, Truncate};
+use super::{
+    optional_setters, validate_logit_bias, validate_num_generations, validate_p, validate_penalty,
+    validate_temperature, FinishReason, GenerateModel, Truncate,
+};
+use crate::{
+    telemetry::{ResponseMeta, ResponseUsage},
+    CohereRequestError,
+};
 
-#[derive(Serialize, Default, Debug)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct GenerateRequest<'input> {
     /// Represents the prompt or text to be completed.
-    pub prompt: &'input str,
+    pub prompt: Cow<'input, str>,
     /// optional - The model to use for text generation. Custom models can also be supplied with their full ID.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<GenerateModel>,
@@ -70,7 +77,78 @@ pub struct GenerateRequest<'input> {
     pub truncate: Option<Truncate>,
 }
 
-#[derive(strum_macros::Display, Serialize, Debug)]
+impl<'input> GenerateRequest<'input> {
+    /// Returns a builder that validates the request parameters when built.
+    pub fn builder() -> GenerateRequestBuilder<'input> {
+        GenerateRequestBuilder::default()
+    }
+
+    /// Converts the request into one that owns all of its data.
+    pub fn into_owned(self) -> GenerateRequest<'static> {
+        GenerateRequest {
+            prompt: Cow::Owned(self.prompt.into_owned()),
+            ..self
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct GenerateRequestBuilder<'input> {
+    prompt: Option<Cow<'input, str>>,
+    request: GenerateRequest<'input>,
+}
+
+impl<'input> GenerateRequestBuilder<'input> {
+    /// Sets the prompt or text to be completed.
+    pub fn prompt(mut self, prompt: impl Into<Cow<'input, str>>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    optional_setters! {
+        model: GenerateModel,
+        max_tokens: u32,
+        preset: String,
+        temperature: f64,
+        num_generations: u8,
+        k: u64,
+        p: f64,
+        frequency_penalty: f64,
+        presence_penalty: f64,
+        end_sequences: Vec<String>,
+        stop_sequences: Vec<String>,
+        return_likelihoods: ReturnLikelihoods,
+        logit_bias: HashMap<u64, f32>,
+        truncate: Truncate,
+    }
+
+    /// Validates the parameters and builds the request.
+    pub fn build(self) -> Result<GenerateRequest<'input>, CohereRequestError> {
+        let prompt = self
+            .prompt
+            .ok_or(CohereRequestError::MissingField("prompt"))?;
+        validate_temperature(self.request.temperature)?;
+        validate_p(self.request.p)?;
+        validate_penalty("frequency_penalty", self.request.frequency_penalty)?;
+        validate_penalty("presence_penalty", self.request.presence_penalty)?;
+        validate_num_generations(self.request.num_generations)?;
+        validate_logit_bias(self.request.logit_bias.as_ref())?;
+
+        Ok(GenerateRequest {
+            prompt,
+            ..self.request
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct GenerateStreamRequest<'input> {
+    #[serde(flatten)]
+    pub request: &'input GenerateRequest<'input>,
+    pub stream: bool,
+}
+
+#[derive(strum_macros::Display, Serialize, Deserialize, Clone, Debug)]
 pub enum ReturnLikelihoods {
     #[strum(serialize = "GENERATION")]
     #[serde(rename = "GENERATION")]
@@ -83,13 +161,35 @@ pub enum ReturnLikelihoods {
     None,
 }
 
-#[derive(Deserialize, Debug)]
-pub(crate) struct GenerateResponse {
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct GenerateResponse {
+    /// The ID of the generate request.
+    pub id: String,
     /// Contains the generations.
     pub generations: Vec<Generation>,
 }
 
+/// A [`GenerateResponse`] with the metadata of the API call, which is only read by the instrumentation.
 #[derive(Deserialize, Debug)]
+pub(crate) struct GenerateApiResponse {
+    pub generations: Vec<Generation>,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub meta: Option<ResponseMeta>,
+}
+
+impl ResponseUsage for GenerateApiResponse {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn meta(&self) -> Option<&ResponseMeta> {
+        self.meta.as_ref()
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
 pub struct Generation {
     /// Contains the generated text.
     pub text: String,
@@ -100,9 +200,12 @@ pub struct Generation {
     /// The likelihood.
     #[serde(default)]
     pub token_likelihoods: Vec<TokenLikelihood>,
+    /// The reason the generation stopped.
+    #[serde(default)]
+    pub finish_reason: Option<FinishReason>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, PartialEq)]
 pub struct TokenLikelihood {
     /// The token.
     pub token: String,
@@ -110,3 +213,22 @@ pub struct TokenLikelihood {
     /// have a likelihood.
     pub likelihood: String,
 }
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(tag = "event_type")]
+pub enum GenerateStreamResponse {
+    #[serde(rename = "text-generation")]
+    GenerateTextGeneration {
+        /// The generation the text belongs to, which is only sent when several generations are requested.
+        #[serde(default)]
+        index: Option<u32>,
+        is_finished: bool,
+        text: String,
+    },
+    #[serde(rename = "stream-end")]
+    GenerateStreamEnd {
+        finish_reason: FinishReason,
+        is_finished: bool,
+        response: GenerateResponse,
+    },
+}
@@ -1,4 +1,8 @@
-use serde::Serialize;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::CohereRequestError;
 
 pub mod chat;
 pub mod classify;
@@ -8,7 +12,84 @@ pub mod generate;
 pub mod rerank;
 pub mod tokenize;
 
-#[derive(strum_macros::Display, Serialize, Debug)]
+/// Generates builder setters for optional request parameters.
+macro_rules! optional_setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            #[doc = concat!("Sets the optional `", stringify!($field), "` parameter.")]
+            pub fn $field(mut self, $field: impl Into<$ty>) -> Self {
+                self.request.$field = Some($field.into());
+                self
+            }
+        )*
+    };
+}
+pub(crate) use optional_setters;
+
+pub(crate) fn validate_temperature(temperature: Option<f64>) -> Result<(), CohereRequestError> {
+    match temperature {
+        Some(t) if t.is_nan() || t < 0.0 => Err(CohereRequestError::InvalidParameter(
+            "temperature",
+            format!("must be non-negative, got {t}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn validate_p(p: Option<f64>) -> Result<(), CohereRequestError> {
+    match p {
+        Some(p) if p.is_nan() || p <= 0.0 || p > 1.0 => Err(CohereRequestError::InvalidParameter(
+            "p",
+            format!("must be in the range (0, 1], got {p}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn validate_penalty(
+    name: &'static str,
+    penalty: Option<f64>,
+) -> Result<(), CohereRequestError> {
+    match penalty {
+        Some(penalty) if penalty.is_nan() || !(0.0..=1.0).contains(&penalty) => {
+            Err(CohereRequestError::InvalidParameter(
+                name,
+                format!("must be between 0 and 1, got {penalty}"),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn validate_num_generations(
+    num_generations: Option<u8>,
+) -> Result<(), CohereRequestError> {
+    match num_generations {
+        Some(n) if !(1..=5).contains(&n) => Err(CohereRequestError::InvalidParameter(
+            "num_generations",
+            format!("must be between 1 and 5, got {n}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn validate_logit_bias(
+    logit_bias: Option<&HashMap<u64, f32>>,
+) -> Result<(), CohereRequestError> {
+    let invalid = logit_bias
+        .into_iter()
+        .flatten()
+        .find(|(_, bias)| bias.is_nan() || !(-10.0..=10.0).contains(*bias));
+    match invalid {
+        Some((token, bias)) => Err(CohereRequestError::InvalidParameter(
+            "logit_bias",
+            format!("bias for token {token} must be between -10 and 10, got {bias}"),
+        )),
+        None => Ok(()),
+    }
+}
+
+#[derive(strum_macros::Display, Serialize, Deserialize, Clone, Debug, Default)]
 pub enum Truncate {
     #[strum(serialize = "NONE")]
     #[serde(rename = "NONE")]
@@ -18,10 +99,11 @@ pub enum Truncate {
     Start,
     #[strum(serialize = "END")]
     #[serde(rename = "END")]
+    #[default]
     End,
 }
 
-#[derive(strum_macros::Display, Serialize, Debug)]
+#[derive(strum_macros::Display, Serialize, Deserialize, Clone, Debug)]
 pub enum EmbedModel {
     #[strum(serialize = "embed-english-light-v2.0")]
     #[serde(rename = "embed-english-light-v2.0")]
@@ -42,10 +124,11 @@ pub enum EmbedModel {
     #[serde(rename = "embed-multilingual-v3.0")]
     MultilingualV3,
     // Custom model
+    #[serde(untagged)]
     Custom(String),
 }
 
-#[derive(strum_macros::Display, Serialize, Debug)]
+#[derive(strum_macros::Display, Serialize, Deserialize, Clone, Debug)]
 pub enum GenerateModel {
     #[strum(serialize = "command")]
     #[serde(rename = "command")]
@@ -68,5 +151,61 @@ pub enum GenerateModel {
     #[strum(serialize = "command-nightly")]
     #[serde(rename = "command-nightly")]
     CommandNightly,
+    #[serde(untagged)]
     Custom(String),
 }
+
+impl GenerateModel {
+    /// The maximum number of tokens the model accepts as input and generates, or `None` for custom models.
+    pub fn context_length(&self) -> Option<usize> {
+        match self {
+            GenerateModel::Command | GenerateModel::CommandLight => Some(4_096),
+            GenerateModel::CommandR
+            | GenerateModel::CommandRPlus
+            | GenerateModel::CommandR082024
+            | GenerateModel::CommandRPlus082024
+            | GenerateModel::CommandNightly => Some(128_000),
+            GenerateModel::Custom(_) => None,
+        }
+    }
+}
+
+#[derive(strum_macros::Display, Deserialize, Clone, Debug, PartialEq)]
+#[serde(from = "String")]
+pub enum FinishReason {
+    #[strum(serialize = "COMPLETE")]
+    Complete,
+    #[strum(serialize = "MAX_TOKENS")]
+    MaxTokens,
+    #[strum(serialize = "ERROR")]
+    Error,
+    #[strum(serialize = "ERROR_TOXIC")]
+    ErrorToxic,
+    #[strum(serialize = "ERROR_LIMIT")]
+    ErrorLimit,
+    #[strum(serialize = "USER_CANCEL")]
+    UserCancel,
+    #[strum(serialize = "STOP_SEQUENCE")]
+    StopSequence,
+    #[strum(serialize = "TOOL_CALL")]
+    ToolCall,
+    // Any finish reason not yet known to this crate
+    #[strum(to_string = "{0}")]
+    Other(String),
+}
+
+impl From<String> for FinishReason {
+    fn from(reason: String) -> Self {
+        match reason.as_str() {
+            "COMPLETE" => FinishReason::Complete,
+            "MAX_TOKENS" => FinishReason::MaxTokens,
+            "ERROR" => FinishReason::Error,
+            "ERROR_TOXIC" => FinishReason::ErrorToxic,
+            "ERROR_LIMIT" => FinishReason::ErrorLimit,
+            "USER_CANCEL" => FinishReason::UserCancel,
+            "STOP_SEQUENCE" => FinishReason::StopSequence,
+            "TOOL_CALL" => FinishReason::ToolCall,
+            _ => FinishReason::Other(reason),
+        }
+    }
+}
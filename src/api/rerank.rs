@@ -1,20 +1,186 @@
-use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 
-#[derive(Serialize, Debug, Default)]
+use serde::{ser::Error as _, Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::optional_setters;
+use crate::{
+    telemetry::{ResponseMeta, ResponseUsage},
+    CohereRequestError,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct ReRankRequest<'input> {
     /// The search query.
-    pub query: &'input str,
-    /// A list of document strings to rerank.
-    pub documents: &'input [String],
+    pub query: Cow<'input, str>,
+    /// A list of documents to rerank, either strings or objects whose `rank_fields` are ranked.
+    pub documents: Cow<'input, [ReRankDocument]>,
     /// The model to use.
     pub model: ReRankModel,
     /// The number of results to return, defaults to the length of the documents.
     pub top_n: Option<u64>,
     // The maximum number of chunks to derive from each document.
     pub max_chunks_per_doc: Option<u64>,
+    /// optional - The fields of object documents to rank by, defaults to `text`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rank_fields: Option<Vec<String>>,
+    /// optional - When enabled, each result includes the document it ranks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub return_documents: Option<bool>,
+}
+
+impl<'input> ReRankRequest<'input> {
+    /// Returns a builder that validates the request parameters when built.
+    pub fn builder() -> ReRankRequestBuilder<'input> {
+        ReRankRequestBuilder::default()
+    }
+
+    /// Converts the request into one that owns all of its data.
+    pub fn into_owned(self) -> ReRankRequest<'static> {
+        ReRankRequest {
+            query: Cow::Owned(self.query.into_owned()),
+            documents: Cow::Owned(self.documents.into_owned()),
+            ..self
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct ReRankRequestBuilder<'input> {
+    query: Option<Cow<'input, str>>,
+    documents: Option<Cow<'input, [ReRankDocument]>>,
+    request: ReRankRequest<'input>,
+}
+
+impl<'input> ReRankRequestBuilder<'input> {
+    /// Sets the search query.
+    pub fn query(mut self, query: impl Into<Cow<'input, str>>) -> Self {
+        self.query = Some(query.into());
+        self
+    }
+
+    /// Sets the documents to rerank.
+    pub fn documents(mut self, documents: impl Into<Cow<'input, [ReRankDocument]>>) -> Self {
+        self.documents = Some(documents.into());
+        self
+    }
+
+    /// Sets the model to use. Defaults to `rerank-english-v3.0`.
+    pub fn model(mut self, model: ReRankModel) -> Self {
+        self.request.model = model;
+        self
+    }
+
+    optional_setters! {
+        top_n: u64,
+        max_chunks_per_doc: u64,
+        rank_fields: Vec<String>,
+        return_documents: bool,
+    }
+
+    /// Validates the parameters and builds the request.
+    pub fn build(self) -> Result<ReRankRequest<'input>, CohereRequestError> {
+        let query = self
+            .query
+            .ok_or(CohereRequestError::MissingField("query"))?;
+        let documents = self
+            .documents
+            .ok_or(CohereRequestError::MissingField("documents"))?;
+
+        Ok(ReRankRequest {
+            query,
+            documents,
+            ..self.request
+        })
+    }
+}
+
+/// The parameters of a rerank request other than its documents, for ranking items with `rerank_items`.
+///
+/// The items are returned themselves, so the documents are never returned in the results.
+#[derive(Clone, Debug, Default)]
+pub struct ReRankOptions<'input> {
+    /// The search query.
+    pub query: Cow<'input, str>,
+    /// The model to use.
+    pub model: ReRankModel,
+    /// The number of results to return, defaults to the number of items.
+    pub top_n: Option<u64>,
+    /// The maximum number of chunks to derive from each item.
+    pub max_chunks_per_doc: Option<u64>,
+    /// The fields of object items to rank by, defaults to `text`.
+    pub rank_fields: Option<Vec<String>>,
+}
+
+impl<'input> ReRankOptions<'input> {
+    /// Creates options that rank items by the query with the default parameters.
+    pub fn new(query: impl Into<Cow<'input, str>>) -> Self {
+        ReRankOptions {
+            query: query.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Builds the request that ranks the documents, borrowing the options and documents.
+    pub(crate) fn request<'request>(
+        &'request self,
+        documents: &'request [ReRankDocument],
+    ) -> ReRankRequest<'request> {
+        ReRankRequest {
+            query: Cow::Borrowed(&self.query),
+            documents: Cow::Borrowed(documents),
+            model: self.model.clone(),
+            top_n: self.top_n,
+            max_chunks_per_doc: self.max_chunks_per_doc,
+            rank_fields: self.rank_fields.clone(),
+            return_documents: None,
+        }
+    }
+}
+
+/// A document to rerank.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum ReRankDocument {
+    /// A text document. Semi-structured data can also be ranked as text, for example after serializing it to YAML.
+    Text(String),
+    /// A document with several fields, which are ranked according to the request's `rank_fields`.
+    Object(Map<String, Value>),
+}
+
+impl ReRankDocument {
+    /// Serializes an item, such as a struct, into a document.
+    /// The item must serialize to a string or an object.
+    pub fn from_serialize<T: Serialize + ?Sized>(item: &T) -> Result<Self, serde_json::Error> {
+        match serde_json::to_value(item)? {
+            Value::String(text) => Ok(ReRankDocument::Text(text)),
+            Value::Object(fields) => Ok(ReRankDocument::Object(fields)),
+            _ => Err(serde_json::Error::custom(
+                "a rerank document must be a string or an object",
+            )),
+        }
+    }
+}
+
+impl From<String> for ReRankDocument {
+    fn from(text: String) -> Self {
+        ReRankDocument::Text(text)
+    }
 }
 
-#[derive(strum_macros::Display, Serialize, Debug, Default)]
+impl From<&str> for ReRankDocument {
+    fn from(text: &str) -> Self {
+        ReRankDocument::Text(text.to_string())
+    }
+}
+
+impl From<Map<String, Value>> for ReRankDocument {
+    fn from(fields: Map<String, Value>) -> Self {
+        ReRankDocument::Object(fields)
+    }
+}
+
+#[derive(strum_macros::Display, Serialize, Deserialize, Clone, Debug, Default)]
 pub enum ReRankModel {
     #[strum(serialize = "rerank-english-v2.0")]
     #[serde(rename = "rerank-english-v2.0")]
@@ -35,6 +201,20 @@ pub enum ReRankModel {
 pub(crate) struct ReRankResponse {
     /// List of ranked documents
     pub results: Vec<ReRankResult>,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub meta: Option<ResponseMeta>,
+}
+
+impl ResponseUsage for ReRankResponse {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn meta(&self) -> Option<&ResponseMeta> {
+        self.meta.as_ref()
+    }
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
@@ -43,4 +223,15 @@ pub struct ReRankResult {
     pub index: u64,
     /// A relevance score assigned to the ranking
     pub relevance_score: f64,
+    /// The ranked document, only returned when `return_documents` is enabled.
+    /// Text documents are returned as an object with a `text` field.
+    #[serde(default)]
+    pub document: Option<Map<String, Value>>,
+}
+
+/// An item ranked by `rerank_items`, with its relevance score.
+#[derive(Debug, PartialEq)]
+pub struct ReRankedItem<T> {
+    pub item: T,
+    pub relevance_score: f64,
 }
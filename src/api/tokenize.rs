@@ -1,16 +1,62 @@
+use std::borrow::Cow;
+
 use serde::{Deserialize, Serialize};
 
-use super::GenerateModel;
+use super::{optional_setters, GenerateModel};
+use crate::{telemetry::ResponseUsage, CohereRequestError};
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct TokenizeRequest<'input> {
     /// The string to be tokenized
-    pub text: &'input str,
+    pub text: Cow<'input, str>,
     /// optional - The model to use for tokenization. Custom models can also be supplied with their full ID.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<GenerateModel>,
 }
 
+impl<'input> TokenizeRequest<'input> {
+    /// Returns a builder that validates the request parameters when built.
+    pub fn builder() -> TokenizeRequestBuilder<'input> {
+        TokenizeRequestBuilder::default()
+    }
+
+    /// Converts the request into one that owns all of its data.
+    pub fn into_owned(self) -> TokenizeRequest<'static> {
+        TokenizeRequest {
+            text: Cow::Owned(self.text.into_owned()),
+            ..self
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct TokenizeRequestBuilder<'input> {
+    text: Option<Cow<'input, str>>,
+    request: TokenizeRequest<'input>,
+}
+
+impl<'input> TokenizeRequestBuilder<'input> {
+    /// Sets the string to be tokenized.
+    pub fn text(mut self, text: impl Into<Cow<'input, str>>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    optional_setters! {
+        model: GenerateModel,
+    }
+
+    /// Validates the parameters and builds the request.
+    pub fn build(self) -> Result<TokenizeRequest<'input>, CohereRequestError> {
+        let text = self.text.ok_or(CohereRequestError::MissingField("text"))?;
+
+        Ok(TokenizeRequest {
+            text,
+            ..self.request
+        })
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct TokenizeResponse {
     /// The tokens
@@ -18,3 +64,5 @@ pub struct TokenizeResponse {
     /// String representations of the tokens
     pub token_strings: Vec<String>,
 }
+
+impl ResponseUsage for TokenizeResponse {}
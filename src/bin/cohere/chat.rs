@@ -0,0 +1,123 @@
+use std::{
+    error::Error,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use clap::Args;
+use cohere_rust::{
+    api::{
+        chat::{ChatRequest, ChatStreamResponse},
+        GenerateModel,
+    },
+    session::ChatSession,
+    Cohere,
+};
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+use crate::model;
+
+const HELP: &str = "Type a message and press enter to send it. /reset starts a new conversation and /exit or Ctrl-D ends the session.";
+
+#[derive(Args)]
+pub struct ChatArgs {
+    /// Sends a single message and prints the reply instead of starting a session.
+    #[arg(long)]
+    message: Option<String>,
+
+    /// The name of the model to use, otherwise the API picks its default model.
+    #[arg(short, long, value_parser = model::<GenerateModel>)]
+    model: Option<GenerateModel>,
+
+    /// Instructions that replace the model's default preamble.
+    #[arg(long)]
+    preamble: Option<String>,
+
+    /// The degree of randomness of the replies, usually between 0 and 1.
+    #[arg(short, long)]
+    temperature: Option<f64>,
+
+    /// The file that stores the lines typed in previous sessions. Defaults to `~/.cohere_history`.
+    /// History is only kept when the input is a terminal.
+    #[arg(long)]
+    history_file: Option<PathBuf>,
+}
+
+pub async fn run(client: &Cohere, args: ChatArgs) -> Result<(), Box<dyn Error>> {
+    let mut session = ChatSession::new(ChatRequest {
+        model: args.model,
+        preamble: args.preamble,
+        temperature: args.temperature,
+        ..Default::default()
+    });
+    if let Some(message) = &args.message {
+        send(client, &mut session, message).await?;
+        return Ok(());
+    }
+
+    // Lines piped in by a script weren't typed by the user, so they aren't remembered
+    let history_file = args
+        .history_file
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cohere_history"))
+        })
+        .filter(|_| io::stdin().is_terminal());
+    let mut editor = DefaultEditor::new()?;
+    if let Some(file) = &history_file {
+        // There is no history the first time a session is started
+        let _ = editor.load_history(file);
+    }
+
+    eprintln!("{HELP}");
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => return Err(error.into()),
+        };
+        let message = line.trim();
+        if message.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(message)?;
+
+        match message {
+            "/exit" | "/quit" => break,
+            "/reset" => {
+                session.history.clear();
+                eprintln!("Started a new conversation");
+            }
+            "/help" => eprintln!("{HELP}"),
+            // A failed message is reported without ending the session
+            message => {
+                if let Err(error) = send(client, &mut session, message).await {
+                    eprintln!("error: {error}");
+                }
+            }
+        }
+    }
+
+    if let Some(file) = &history_file {
+        editor.save_history(file)?;
+    }
+    Ok(())
+}
+
+/// Sends the message with the conversation so far, printing the reply as it is streamed.
+async fn send(
+    client: &Cohere,
+    session: &mut ChatSession,
+    message: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut stream = session.send_stream(client, message).await?;
+    let mut stdout = io::stdout();
+    while let Some(response) = stream.recv().await {
+        if let ChatStreamResponse::ChatTextGeneration { text, .. } = response? {
+            write!(stdout, "{text}")?;
+            stdout.flush()?;
+        }
+    }
+    writeln!(stdout)?;
+    Ok(())
+}
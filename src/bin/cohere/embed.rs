@@ -0,0 +1,150 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use clap::{Args, ValueEnum};
+use cohere_rust::{
+    api::{embed::EmbedRequest, EmbedModel, Truncate},
+    Cohere,
+};
+use serde_json::json;
+
+use crate::{model, Input};
+
+#[derive(Args)]
+pub struct EmbedArgs {
+    /// The name of the model to use, otherwise the API picks its default model.
+    #[arg(short, long, value_parser = model::<EmbedModel>)]
+    model: Option<EmbedModel>,
+
+    /// How to handle texts longer than the model's maximum input length.
+    #[arg(long, value_enum, default_value_t = TruncateArg::End)]
+    truncate: TruncateArg,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// The file to write the embeddings to instead of stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// The number of texts embedded by each request.
+    #[arg(long, default_value_t = 96)]
+    batch_size: usize,
+
+    #[command(flatten)]
+    texts: Input,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TruncateArg {
+    None,
+    Start,
+    End,
+}
+
+impl From<TruncateArg> for Truncate {
+    fn from(truncate: TruncateArg) -> Self {
+        match truncate {
+            TruncateArg::None => Truncate::None,
+            TruncateArg::Start => Truncate::Start,
+            TruncateArg::End => Truncate::End,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// A JSON array with the text and embedding of each line.
+    Json,
+    /// A NumPy array with one row per line.
+    Npy,
+    /// A CSV row per line, with the text followed by its embedding.
+    Csv,
+}
+
+pub async fn run(client: &Cohere, args: EmbedArgs) -> Result<(), Box<dyn Error>> {
+    let texts = args.texts.lines()?;
+
+    let mut embeddings = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(args.batch_size.max(1)) {
+        let request = EmbedRequest {
+            model: args.model.clone(),
+            texts: batch.into(),
+            truncate: args.truncate.into(),
+        };
+        embeddings.extend(client.embed(&request).await?);
+    }
+
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    match args.format {
+        Format::Json => write_json(&mut output, &texts, &embeddings)?,
+        Format::Npy => write_npy(&mut output, &embeddings)?,
+        Format::Csv => write_csv(&mut output, &texts, &embeddings)?,
+    }
+    output.flush()?;
+    Ok(())
+}
+
+fn write_json(
+    output: &mut impl Write,
+    texts: &[String],
+    embeddings: &[Vec<f64>],
+) -> io::Result<()> {
+    let embeddings = texts
+        .iter()
+        .zip(embeddings)
+        .map(|(text, embedding)| json!({ "text": text, "embedding": embedding }))
+        .collect::<Vec<_>>();
+    serde_json::to_writer_pretty(&mut *output, &embeddings)?;
+    writeln!(output)
+}
+
+fn write_csv(output: &mut impl Write, texts: &[String], embeddings: &[Vec<f64>]) -> io::Result<()> {
+    for (text, embedding) in texts.iter().zip(embeddings) {
+        write!(output, "\"{}\"", text.replace('"', "\"\""))?;
+        for value in embedding {
+            write!(output, ",{value}")?;
+        }
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+/// Writes the embeddings as a 2-dimensional array of little-endian `f64` in the NumPy `.npy` format.
+fn write_npy(output: &mut impl Write, embeddings: &[Vec<f64>]) -> io::Result<()> {
+    let dimensions = embeddings.first().map_or(0, Vec::len);
+    if embeddings
+        .iter()
+        .any(|embedding| embedding.len() != dimensions)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "embeddings with different dimensions can't be written as an array",
+        ));
+    }
+
+    // The header is padded with spaces so the data starts at a multiple of 64 bytes
+    let mut header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {dimensions}), }}",
+        embeddings.len()
+    );
+    let prefix = b"\x93NUMPY\x01\x00".len() + 2;
+    let padding = 63 - (prefix + header.len()) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    output.write_all(b"\x93NUMPY\x01\x00")?;
+    output.write_all(&(header.len() as u16).to_le_bytes())?;
+    output.write_all(header.as_bytes())?;
+    for value in embeddings.iter().flatten() {
+        output.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,268 @@
+//! The `cohere` command-line interface, enabled by the `cli` feature.
+
+mod chat;
+mod embed;
+
+use std::{
+    error::Error,
+    fs,
+    io::{self, BufRead, Read},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use clap::{Args, Parser, Subcommand};
+use cohere_rust::{
+    api::{
+        classify::{ClassifyExample, ClassifyRequest},
+        detokenize::DetokenizeRequest,
+        rerank::{ReRankModel, ReRankRequest},
+        tokenize::TokenizeRequest,
+        EmbedModel, GenerateModel,
+    },
+    Cohere,
+};
+use serde::de::DeserializeOwned;
+
+/// Interact with the Cohere API from the shell.
+#[derive(Parser)]
+#[command(name = "cohere", version)]
+struct Cli {
+    /// The API key, which is required by every command.
+    #[arg(long, env = "COHERE_API_KEY", hide_env_values = true, global = true)]
+    api_key: Option<String>,
+
+    /// The base URL of the API.
+    #[arg(long, env = "COHERE_API_URL", global = true)]
+    api_url: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Chat with a model in an interactive session, or send a single message with `--message`.
+    Chat(chat::ChatArgs),
+    /// Embed lines of text read from files or stdin.
+    Embed(embed::EmbedArgs),
+    /// Rank documents, one per line, by their relevance to a query.
+    Rerank(RerankArgs),
+    /// Classify lines of text using labeled examples.
+    Classify(ClassifyArgs),
+    /// Split text into tokens, printing the ID and text of each token.
+    Tokenize(TokenizeArgs),
+    /// Convert token IDs back into text.
+    Detokenize(DetokenizeArgs),
+    /// Check that the API key is valid.
+    CheckKey,
+}
+
+/// Files to read lines of text from.
+#[derive(Args)]
+pub(crate) struct Input {
+    /// Files with one text per line. Reads stdin if no file is given.
+    files: Vec<PathBuf>,
+}
+
+impl Input {
+    /// Reads every non-empty line of the files, or of stdin if there aren't any.
+    pub(crate) fn lines(&self) -> io::Result<Vec<String>> {
+        let mut text = String::new();
+        if self.files.is_empty() {
+            io::stdin().read_to_string(&mut text)?;
+        }
+        for file in &self.files {
+            text.push_str(&fs::read_to_string(file)?);
+            text.push('\n');
+        }
+        Ok(text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+#[derive(Args)]
+struct RerankArgs {
+    /// The query to rank documents against.
+    #[arg(short, long)]
+    query: String,
+
+    /// The name of the model to use. Defaults to `rerank-english-v3.0`.
+    #[arg(short, long, value_parser = model::<ReRankModel>)]
+    model: Option<ReRankModel>,
+
+    /// Only print the most relevant documents.
+    #[arg(short = 'n', long)]
+    top_n: Option<u64>,
+
+    #[command(flatten)]
+    documents: Input,
+}
+
+#[derive(Args)]
+struct ClassifyArgs {
+    /// A labeled example written as `LABEL:TEXT`.
+    #[arg(short, long = "example", value_parser = example)]
+    examples: Vec<(String, String)>,
+
+    /// A JSON Lines file of examples with `text` and `label` fields.
+    #[arg(long)]
+    examples_file: Option<PathBuf>,
+
+    /// The name of the model to use, otherwise the API picks its default model.
+    #[arg(short, long, value_parser = model::<EmbedModel>)]
+    model: Option<EmbedModel>,
+
+    #[command(flatten)]
+    inputs: Input,
+}
+
+#[derive(Args)]
+struct TokenizeArgs {
+    /// The text to tokenize. Reads stdin if no text is given.
+    text: Option<String>,
+
+    /// The name of the model to use, otherwise the API picks its default model.
+    #[arg(short, long, value_parser = model::<GenerateModel>)]
+    model: Option<GenerateModel>,
+}
+
+#[derive(Args)]
+struct DetokenizeArgs {
+    /// The token IDs to convert.
+    #[arg(required = true)]
+    tokens: Vec<u64>,
+
+    /// The name of the model to use, otherwise the API picks its default model.
+    #[arg(short, long, value_parser = model::<GenerateModel>)]
+    model: Option<GenerateModel>,
+}
+
+/// Parses a model by the name the API uses for it.
+pub(crate) fn model<M: DeserializeOwned>(name: &str) -> Result<M, String> {
+    serde_json::from_value(name.into()).map_err(|_| format!("unknown model `{name}`"))
+}
+
+fn example(example: &str) -> Result<(String, String), String> {
+    example
+        .split_once(':')
+        .map(|(label, text)| (label.trim().to_string(), text.trim().to_string()))
+        .ok_or_else(|| format!("expected `LABEL:TEXT`, got `{example}`"))
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let api_key = cli
+        .api_key
+        .ok_or("please provide an API key with `--api-key` or the COHERE_API_KEY env variable")?;
+    let mut builder = Cohere::builder().api_key(api_key);
+    if let Some(api_url) = cli.api_url {
+        builder = builder.api_url(api_url);
+    }
+    let client = builder.build();
+
+    match cli.command {
+        Command::Chat(args) => chat::run(&client, args).await,
+        Command::Embed(args) => embed::run(&client, args).await,
+        Command::Rerank(args) => {
+            let documents = args.documents.lines()?;
+            let request = ReRankRequest {
+                query: args.query.into(),
+                documents: documents
+                    .iter()
+                    .map(|document| document.as_str().into())
+                    .collect::<Vec<_>>()
+                    .into(),
+                model: args.model.unwrap_or_default(),
+                top_n: args.top_n,
+                ..Default::default()
+            };
+            for result in client.rerank(&request).await? {
+                let document = documents.get(result.index as usize).ok_or_else(|| {
+                    format!(
+                        "the API ranked a document that doesn't exist: {}",
+                        result.index
+                    )
+                })?;
+                println!(
+                    "{:.4}\t{}\t{document}",
+                    result.relevance_score, result.index
+                );
+            }
+            Ok(())
+        }
+        Command::Classify(args) => {
+            let mut examples = args
+                .examples
+                .into_iter()
+                .map(|(label, text)| ClassifyExample {
+                    text: text.into(),
+                    label: label.into(),
+                })
+                .collect::<Vec<_>>();
+            if let Some(file) = args.examples_file {
+                for line in io::BufReader::new(fs::File::open(file)?).lines() {
+                    let line = line?;
+                    if !line.trim().is_empty() {
+                        examples.push(serde_json::from_str(&line)?);
+                    }
+                }
+            }
+            let request = ClassifyRequest {
+                model: args.model,
+                inputs: args.inputs.lines()?.into(),
+                examples: examples.into(),
+                ..Default::default()
+            };
+            for classification in client.classify(&request).await? {
+                println!(
+                    "{}\t{:.4}\t{}",
+                    classification.prediction, classification.confidence, classification.input
+                );
+            }
+            Ok(())
+        }
+        Command::Tokenize(args) => {
+            let text = match args.text {
+                Some(text) => text,
+                None => io::read_to_string(io::stdin())?,
+            };
+            let request = TokenizeRequest {
+                text: text.into(),
+                model: args.model,
+            };
+            let response = client.tokenize(&request).await?;
+            for (token, text) in response.tokens.iter().zip(&response.token_strings) {
+                println!("{token}\t{text}");
+            }
+            Ok(())
+        }
+        Command::Detokenize(args) => {
+            let request = DetokenizeRequest {
+                tokens: args.tokens.into(),
+                model: args.model,
+            };
+            println!("{}", client.detokenize(&request).await?);
+            Ok(())
+        }
+        Command::CheckKey => {
+            client.check_api_key().await?;
+            println!("API key is valid");
+            Ok(())
+        }
+    }
+}
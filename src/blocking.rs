@@ -0,0 +1,184 @@
+//! A blocking client for synchronous code, similar to `reqwest::blocking`.
+//!
+//! The client runs the async client on its own single-threaded runtime, so it must not be used
+//! from within an async runtime.
+
+use std::sync::Arc;
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{
+    api::{
+        chat::{ChatRequest, ChatStreamResponse},
+        classify::{Classification, ClassifyRequest},
+        detokenize::DetokenizeRequest,
+        embed::EmbedRequest,
+        generate::{GenerateRequest, GenerateStreamResponse, Generation},
+        rerank::{ReRankOptions, ReRankRequest, ReRankResult, ReRankedItem},
+        tokenize::{TokenizeRequest, TokenizeResponse},
+    },
+    concurrency::ConcurrencyStats,
+    CohereApiError, CohereStream, CohereStreamError,
+};
+
+/// A blocking version of [`crate::Cohere`].
+///
+/// A blocking client can be created from an async client configured with [`crate::CohereBuilder`]:
+///
+/// ```no_run
+/// let co: cohere_rust::blocking::Cohere = cohere_rust::Cohere::builder().build().into();
+/// ```
+pub struct Cohere {
+    inner: crate::Cohere,
+    runtime: Arc<Runtime>,
+}
+
+impl Default for Cohere {
+    fn default() -> Self {
+        crate::Cohere::default().into()
+    }
+}
+
+impl From<crate::Cohere> for Cohere {
+    fn from(inner: crate::Cohere) -> Self {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to create the runtime for the blocking client");
+
+        Cohere {
+            inner,
+            runtime: Arc::new(runtime),
+        }
+    }
+}
+
+impl Cohere {
+    pub fn new<U: Into<String>, K: Into<String>>(api_url: U, api_key: K) -> Self {
+        crate::Cohere::new(api_url, api_key).into()
+    }
+
+    pub fn concurrency_stats(&self) -> ConcurrencyStats {
+        self.inner.concurrency_stats()
+    }
+
+    /// Verify that the Cohere API key being used is valid
+    pub fn check_api_key(&self) -> Result<(), CohereApiError> {
+        self.runtime.block_on(self.inner.check_api_key())
+    }
+
+    /// Generates realistic text conditioned on a given input.
+    pub fn generate<'input>(
+        &self,
+        request: &GenerateRequest<'input>,
+    ) -> Result<Vec<Generation>, CohereApiError> {
+        self.runtime.block_on(self.inner.generate(request))
+    }
+
+    /// Generates realistic text conditioned on a given input, streaming the generated text as it is produced.
+    pub fn generate_stream<'input>(
+        &self,
+        request: &GenerateRequest<'input>,
+    ) -> Result<StreamIter<GenerateStreamResponse>, CohereApiError> {
+        let stream = self.runtime.block_on(self.inner.generate_stream(request))?;
+
+        Ok(self.stream(stream))
+    }
+
+    /// Chat with Cohere's LLM
+    pub fn chat<'input>(
+        &self,
+        request: &ChatRequest<'input>,
+    ) -> Result<StreamIter<ChatStreamResponse>, CohereApiError> {
+        let stream = self.runtime.block_on(self.inner.chat(request))?;
+
+        Ok(self.stream(stream))
+    }
+
+    /// Returns text embeddings.
+    /// An embedding is a list of floating point numbers that captures semantic information about the text that it represents.
+    /// Embeddings can be used to create text classifiers as well as empower semantic search.
+    pub fn embed<'input>(
+        &self,
+        request: &EmbedRequest<'input>,
+    ) -> Result<Vec<Vec<f64>>, CohereApiError> {
+        self.runtime.block_on(self.inner.embed(request))
+    }
+
+    /// Makes a prediction about which label fits the specified text inputs best.
+    /// To make a prediction, classify uses the provided examples of text + label pairs as a reference.
+    pub fn classify<'input>(
+        &self,
+        request: &ClassifyRequest<'input>,
+    ) -> Result<Vec<Classification>, CohereApiError> {
+        self.runtime.block_on(self.inner.classify(request))
+    }
+
+    /// Splits input text into smaller units called tokens using byte-pair encoding (BPE).
+    pub fn tokenize<'input>(
+        &self,
+        request: &TokenizeRequest<'input>,
+    ) -> Result<TokenizeResponse, CohereApiError> {
+        self.runtime.block_on(self.inner.tokenize(request))
+    }
+
+    /// Takes tokens using byte-pair encoding and returns their text representation.
+    pub fn detokenize<'input>(
+        &self,
+        request: &DetokenizeRequest<'input>,
+    ) -> Result<String, CohereApiError> {
+        self.runtime.block_on(self.inner.detokenize(request))
+    }
+
+    /// Takes a query plus an list of texts and return an ordered array with each text assigned a relevance score.
+    pub fn rerank<'input>(
+        &self,
+        request: &ReRankRequest<'input>,
+    ) -> Result<Vec<ReRankResult>, CohereApiError> {
+        self.runtime.block_on(self.inner.rerank(request))
+    }
+
+    /// Reranks items such as structs, returning them from most to least relevant with their relevance scores.
+    /// The items are ranked as the documents of a request with the options, and must serialize to strings or objects.
+    /// A result that doesn't rank exactly one of the items fails with `CohereApiError::TransportError`.
+    pub fn rerank_items<'input, T: Serialize + Send>(
+        &self,
+        options: &ReRankOptions<'input>,
+        items: Vec<T>,
+    ) -> Result<Vec<ReRankedItem<T>>, CohereApiError> {
+        self.runtime
+            .block_on(self.inner.rerank_items(options, items))
+    }
+
+    /// Loads the tokenizer of a model, whose `tokenizer_url` is looked up with the models API.
+    #[cfg(feature = "local-tokenizer")]
+    pub fn local_tokenizer(
+        &self,
+        model: &crate::api::GenerateModel,
+    ) -> Result<crate::tokenizer::LocalTokenizer, crate::tokenizer::TokenizerError> {
+        self.runtime.block_on(self.inner.local_tokenizer(model))
+    }
+
+    fn stream<T>(&self, stream: CohereStream<T>) -> StreamIter<T> {
+        StreamIter {
+            runtime: self.runtime.clone(),
+            stream,
+        }
+    }
+}
+
+/// An iterator over the messages of a streamed response.
+/// The response is only read from the API while the iterator is advanced.
+pub struct StreamIter<T> {
+    runtime: Arc<Runtime>,
+    stream: CohereStream<T>,
+}
+
+impl<T: DeserializeOwned> Iterator for StreamIter<T> {
+    type Item = Result<T, CohereStreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.recv())
+    }
+}
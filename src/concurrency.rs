@@ -0,0 +1,192 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{CohereApiError, Endpoint};
+
+/// Limits on the number of requests the client sends at the same time.
+/// Requests over a limit wait in a first-in, first-out queue until an in-flight request completes.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ConcurrencyConfig {
+    /// optional - The maximum number of requests in flight across the whole client.
+    #[serde(default)]
+    pub max_in_flight: Option<usize>,
+    /// optional - The maximum number of requests in flight for a specific endpoint.
+    #[serde(default)]
+    pub endpoints: HashMap<Endpoint, usize>,
+    /// optional - The maximum number of requests allowed to wait for a limit.
+    /// Requests beyond this fail immediately with `CohereApiError::QueueFull`.
+    #[serde(default)]
+    pub max_queue_size: Option<usize>,
+}
+
+/// A snapshot of the requests currently in flight and waiting for a concurrency limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QueueStats {
+    /// The number of requests currently being sent or streamed.
+    pub in_flight: usize,
+    /// The number of requests waiting for a request in flight to complete.
+    pub queued: usize,
+    /// The configured maximum number of requests in flight.
+    pub max_in_flight: usize,
+}
+
+/// Queue statistics for the client-wide limit and each endpoint limit.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConcurrencyStats {
+    pub client: Option<QueueStats>,
+    pub endpoints: HashMap<Endpoint, QueueStats>,
+}
+
+pub(crate) struct ConcurrencyLimiter {
+    client: Option<Limit>,
+    endpoints: HashMap<Endpoint, Limit>,
+    max_queue_size: Option<usize>,
+}
+
+/// Holds a request's place in the concurrency limits until it is dropped.
+pub(crate) struct ConcurrencyPermit {
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(config: ConcurrencyConfig) -> Self {
+        ConcurrencyLimiter {
+            client: config.max_in_flight.map(|max| Limit::new(max, None)),
+            endpoints: config
+                .endpoints
+                .into_iter()
+                .map(|(endpoint, max)| (endpoint, Limit::new(max, Some(endpoint))))
+                .collect(),
+            max_queue_size: config.max_queue_size,
+        }
+    }
+
+    /// Waits until the request can be sent without going over the client or endpoint limits.
+    pub async fn acquire(&self, endpoint: Endpoint) -> Result<ConcurrencyPermit, CohereApiError> {
+        let mut permits = Vec::with_capacity(2);
+        // The endpoint permit is acquired first so that waiting on a busy endpoint
+        // doesn't hold up requests to other endpoints
+        for limit in self
+            .endpoints
+            .get(&endpoint)
+            .into_iter()
+            .chain(&self.client)
+        {
+            permits.push(limit.acquire(self.max_queue_size).await?);
+        }
+
+        Ok(ConcurrencyPermit { _permits: permits })
+    }
+
+    pub fn stats(&self) -> ConcurrencyStats {
+        ConcurrencyStats {
+            client: self.client.as_ref().map(Limit::stats),
+            endpoints: self
+                .endpoints
+                .iter()
+                .map(|(endpoint, limit)| (*endpoint, limit.stats()))
+                .collect(),
+        }
+    }
+}
+
+struct Limit {
+    semaphore: Arc<Semaphore>,
+    queued: AtomicUsize,
+    max: usize,
+    /// The endpoint the limit applies to, or `None` for the client-wide limit.
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    endpoint: Option<Endpoint>,
+}
+
+impl Limit {
+    fn new(max: usize, endpoint: Option<Endpoint>) -> Self {
+        let max = max.max(1);
+        Limit {
+            semaphore: Arc::new(Semaphore::new(max)),
+            queued: AtomicUsize::new(0),
+            max,
+            endpoint,
+        }
+    }
+
+    async fn acquire(
+        &self,
+        max_queue_size: Option<usize>,
+    ) -> Result<OwnedSemaphorePermit, CohereApiError> {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        // Leave the queue even if the request is cancelled while waiting
+        let queue = QueueGuard::join(self);
+        if max_queue_size.is_some_and(|max| queue.ahead >= max) {
+            return Err(CohereApiError::QueueFull);
+        }
+
+        // The semaphore is fair, so waiting requests are let through in the order they arrived
+        Ok(self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("concurrency limit semaphore should never be closed"))
+    }
+
+    fn stats(&self) -> QueueStats {
+        QueueStats {
+            in_flight: self.max - self.semaphore.available_permits(),
+            queued: self.queued.load(Ordering::SeqCst),
+            max_in_flight: self.max,
+        }
+    }
+}
+
+/// A request's place in the queue of a limit, which it leaves when dropped.
+struct QueueGuard<'a> {
+    queued: &'a AtomicUsize,
+    /// The number of requests that were already waiting when the request joined the queue.
+    ahead: usize,
+    #[cfg(feature = "metrics")]
+    depth: metrics::Gauge,
+}
+
+impl<'a> QueueGuard<'a> {
+    fn join(limit: &'a Limit) -> Self {
+        let ahead = limit.queued.fetch_add(1, Ordering::SeqCst);
+        #[cfg(feature = "metrics")]
+        let depth = match limit.endpoint {
+            Some(endpoint) => metrics::gauge!(
+                "cohere_queue_depth",
+                "limit" => "endpoint",
+                "endpoint" => endpoint.to_string()
+            ),
+            None => metrics::gauge!("cohere_queue_depth", "limit" => "client"),
+        };
+        #[cfg(feature = "metrics")]
+        depth.increment(1.0);
+
+        QueueGuard {
+            queued: &limit.queued,
+            ahead,
+            #[cfg(feature = "metrics")]
+            depth,
+        }
+    }
+}
+
+impl Drop for QueueGuard<'_> {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        #[cfg(feature = "metrics")]
+        self.depth.decrement(1.0);
+    }
+}
@@ -0,0 +1,345 @@
+//! Client-side truncation of chat history to keep requests under a token budget.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    fmt,
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        chat::{ChatMessage, ChatRequest, ChatStreamResponse},
+        tokenize::TokenizeRequest,
+        GenerateModel,
+    },
+    CohereApi, CohereApiError,
+};
+
+/// The context length assumed when the model is unknown, which is the smallest of the known models.
+const DEFAULT_CONTEXT_LENGTH: usize = 4_096;
+/// The number of tokens left for the reply when the request doesn't set `max_tokens`.
+const DEFAULT_REPLY_TOKENS: usize = 1_024;
+
+/// Token counts by a hash of the model and text.
+type TokenCounts = HashMap<u64, usize>;
+
+const SUMMARY_PROMPT: &str = "Summarize the following conversation in a few sentences, keeping any facts, names and decisions that may be needed to continue it.";
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:";
+
+/// Counts the tokens of text as the model would tokenize it.
+///
+/// Every [`CohereApi`] counts tokens with the `tokenize` endpoint.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait TokenCounter: Send + Sync {
+    async fn count_tokens(
+        &self,
+        text: &str,
+        model: Option<&GenerateModel>,
+    ) -> Result<usize, CohereApiError>;
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl<C: CohereApi + ?Sized> TokenCounter for C {
+    async fn count_tokens(
+        &self,
+        text: &str,
+        model: Option<&GenerateModel>,
+    ) -> Result<usize, CohereApiError> {
+        // The endpoint rejects empty text
+        if text.is_empty() {
+            return Ok(0);
+        }
+        let request = TokenizeRequest {
+            text: text.into(),
+            model: model.cloned(),
+        };
+        Ok(self.tokenize(&request).await?.tokens.len())
+    }
+}
+
+/// How the oldest turns of the history are removed when it doesn't fit in the budget.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum TruncationStrategy {
+    /// Drops the oldest turns until the history fits.
+    DropOldest,
+    /// Replaces all but the most recent `keep_turns` turns with a summary written by the model,
+    /// which is kept as a `SYSTEM` message. Turns are still dropped if the history doesn't fit after summarizing.
+    Summarize { keep_turns: usize },
+}
+
+/// Truncates chat history so that the preamble, tools, history and message of a request fit in a token budget.
+///
+/// The budget is the request's `max_input_tokens` if set, otherwise the model's context length minus the
+/// request's `max_tokens` (or 1024 tokens) left for the reply. The context length of 4096 tokens is assumed
+/// for custom models and when no model is set.
+///
+/// History is removed a turn at a time, a turn being a `USER` message with the replies and tool results that
+/// follow it, so tool calls are never separated from their results. Token counts are cached, so each message is
+/// only counted once, and the cache only keeps the texts that are still sent after truncating.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryTruncation {
+    pub strategy: TruncationStrategy,
+    /// Keeps `SYSTEM` messages, including summaries, when turns are dropped. Enabled by default.
+    #[serde(default = "default_keep_system_messages")]
+    pub keep_system_messages: bool,
+    #[serde(skip)]
+    counter: Option<Arc<dyn TokenCounter>>,
+    #[serde(skip)]
+    counts: Arc<Mutex<TokenCounts>>,
+}
+
+impl HistoryTruncation {
+    pub fn new(strategy: TruncationStrategy) -> Self {
+        HistoryTruncation {
+            strategy,
+            keep_system_messages: true,
+            counter: None,
+            counts: Arc::default(),
+        }
+    }
+
+    /// Counts tokens with the counter, for example a local tokenizer, instead of the client's `tokenize` endpoint.
+    pub fn token_counter<T: TokenCounter + 'static>(mut self, counter: T) -> Self {
+        self.counter = Some(Arc::new(counter));
+        self
+    }
+
+    /// Removes the oldest turns of the history until the request fits in the budget.
+    ///
+    /// The `chat_history` of the request is ignored; the history is truncated in place instead.
+    /// Summaries are written with the client's `chat` endpoint.
+    pub async fn truncate<C: CohereApi + ?Sized>(
+        &self,
+        client: &C,
+        request: &ChatRequest<'_>,
+        history: &mut Vec<ChatMessage>,
+    ) -> Result<(), CohereApiError> {
+        let model = request.model.as_ref();
+        let mut fixed_texts = vec![request.message.to_string()];
+        fixed_texts.extend(request.preamble.clone());
+        if let Some(tools) = &request.tools {
+            fixed_texts.push(serde_json::to_string(tools)?);
+        }
+        if let Some(tool_results) = &request.tool_results {
+            fixed_texts.push(serde_json::to_string(tool_results)?);
+        }
+
+        let result = self
+            .truncate_history(client, request, &fixed_texts, history)
+            .await;
+
+        // Texts that are no longer sent are forgotten, so the cache doesn't grow with the conversation
+        let mut kept: HashSet<u64> = fixed_texts.iter().map(|text| key(model, text)).collect();
+        for message in history.iter() {
+            kept.insert(key(model, &message_text(message)?));
+        }
+        self.counts
+            .lock()
+            .expect("token count lock poisoned")
+            .retain(|key, _| kept.contains(key));
+        result
+    }
+
+    async fn truncate_history<C: CohereApi + ?Sized>(
+        &self,
+        client: &C,
+        request: &ChatRequest<'_>,
+        fixed_texts: &[String],
+        history: &mut Vec<ChatMessage>,
+    ) -> Result<(), CohereApiError> {
+        let model = request.model.as_ref();
+        let budget = match request.max_input_tokens {
+            Some(max_input_tokens) => max_input_tokens as usize,
+            None => model
+                .and_then(GenerateModel::context_length)
+                .unwrap_or(DEFAULT_CONTEXT_LENGTH)
+                .saturating_sub(
+                    request
+                        .max_tokens
+                        .map_or(DEFAULT_REPLY_TOKENS, |max| max as usize),
+                ),
+        };
+
+        let mut fixed = 0;
+        for text in fixed_texts {
+            fixed += self.count(client, text, model).await?;
+        }
+
+        if fixed + self.count_history(client, history, model).await? <= budget {
+            return Ok(());
+        }
+
+        if let TruncationStrategy::Summarize { keep_turns } = self.strategy {
+            let turns = turns(history);
+            let end = turns
+                .len()
+                .checked_sub(keep_turns)
+                .and_then(|index| turns.get(index))
+                .map_or(history.len(), |&(start, _)| start);
+            let summarized = matches!(
+                &history[..end],
+                [ChatMessage::System { message }] if message.starts_with(SUMMARY_PREFIX)
+            );
+            if turns.len() > keep_turns && !summarized {
+                let summary = summarize(client, model, &history[..end]).await?;
+                history.splice(
+                    ..end,
+                    [ChatMessage::System {
+                        message: format!("{SUMMARY_PREFIX} {summary}"),
+                    }],
+                );
+            }
+        }
+
+        while fixed + self.count_history(client, history, model).await? > budget {
+            let droppable = turns(history).into_iter().find(|&(start, end)| {
+                !self.keep_system_messages || !is_system(&history[start..end])
+            });
+            match droppable {
+                Some((start, end)) => {
+                    history.drain(start..end);
+                }
+                // The request doesn't fit even without history, which is left to the API to reject
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    async fn count_history<C: CohereApi + ?Sized>(
+        &self,
+        client: &C,
+        history: &[ChatMessage],
+        model: Option<&GenerateModel>,
+    ) -> Result<usize, CohereApiError> {
+        let mut total = 0;
+        for message in history {
+            total += self.count(client, &message_text(message)?, model).await?;
+        }
+        Ok(total)
+    }
+
+    async fn count<C: CohereApi + ?Sized>(
+        &self,
+        client: &C,
+        text: &str,
+        model: Option<&GenerateModel>,
+    ) -> Result<usize, CohereApiError> {
+        let key = key(model, text);
+        if let Some(count) = self
+            .counts
+            .lock()
+            .expect("token count lock poisoned")
+            .get(&key)
+        {
+            return Ok(*count);
+        }
+
+        let count = match &self.counter {
+            Some(counter) => counter.count_tokens(text, model).await?,
+            None => client.count_tokens(text, model).await?,
+        };
+        self.counts
+            .lock()
+            .expect("token count lock poisoned")
+            .insert(key, count);
+        Ok(count)
+    }
+}
+
+impl fmt::Debug for HistoryTruncation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HistoryTruncation")
+            .field("strategy", &self.strategy)
+            .field("keep_system_messages", &self.keep_system_messages)
+            .field("token_counter", &self.counter.is_some())
+            .finish()
+    }
+}
+
+fn default_keep_system_messages() -> bool {
+    true
+}
+
+/// The key of the token count of a text for a model in the cache.
+fn key(model: Option<&GenerateModel>, text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    // Custom models are told apart by their name, which isn't part of their display name
+    model.map(|model| format!("{model:?}")).hash(&mut hasher);
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The text of a message as it is counted against the budget.
+fn message_text(message: &ChatMessage) -> Result<String, serde_json::Error> {
+    Ok(match message {
+        ChatMessage::User { message } | ChatMessage::System { message } => message.clone(),
+        ChatMessage::Chatbot {
+            message,
+            tool_calls,
+        } if tool_calls.is_empty() => message.clone(),
+        ChatMessage::Chatbot {
+            message,
+            tool_calls,
+        } => format!("{message}{}", serde_json::to_string(tool_calls)?),
+        ChatMessage::Tool { tool_results } => serde_json::to_string(tool_results)?,
+    })
+}
+
+fn is_system(turn: &[ChatMessage]) -> bool {
+    matches!(turn, [ChatMessage::System { .. }])
+}
+
+/// Splits the history into the ranges of its turns. Each `SYSTEM` message is a turn of its own.
+fn turns(history: &[ChatMessage]) -> Vec<(usize, usize)> {
+    let mut turns: Vec<(usize, usize)> = Vec::new();
+    for (index, message) in history.iter().enumerate() {
+        let continues_turn = match (message, turns.last()) {
+            (ChatMessage::User { .. } | ChatMessage::System { .. }, _) | (_, None) => false,
+            (_, Some(&(start, _))) => !matches!(history[start], ChatMessage::System { .. }),
+        };
+        match turns.last_mut() {
+            Some((_, end)) if continues_turn => *end = index + 1,
+            _ => turns.push((index, index + 1)),
+        }
+    }
+    turns
+}
+
+/// Asks the model to summarize the messages.
+async fn summarize<C: CohereApi + ?Sized>(
+    client: &C,
+    model: Option<&GenerateModel>,
+    messages: &[ChatMessage],
+) -> Result<String, CohereApiError> {
+    let mut transcript = String::new();
+    for message in messages {
+        let role = match message {
+            ChatMessage::User { .. } => "User",
+            ChatMessage::Chatbot { .. } => "Chatbot",
+            ChatMessage::System { .. } => "System",
+            ChatMessage::Tool { .. } => "Tool",
+        };
+        transcript.push_str(&format!("{role}: {}\n", message_text(message)?));
+    }
+
+    let request = ChatRequest {
+        message: format!("{SUMMARY_PROMPT}\n\n{transcript}").into(),
+        model: model.cloned(),
+        ..Default::default()
+    };
+    let mut stream = client.chat(&request).await?;
+    let mut summary = String::new();
+    while let Some(message) = stream.recv().await {
+        if let ChatStreamResponse::ChatTextGeneration { text, .. } = message? {
+            summary.push_str(&text);
+        }
+    }
+    Ok(summary)
+}
@@ -1,25 +1,57 @@
-use std::time::Duration;
+use std::sync::Arc;
 
 use api::{
     chat::{ChatRequest, ChatStreamRequest, ChatStreamResponse},
     classify::{Classification, ClassifyRequest, ClassifyResponse},
     detokenize::{DetokenizeRequest, DetokenizeResponse},
     embed::{EmbedRequest, EmbedResponse},
-    generate::{GenerateRequest, GenerateResponse, Generation},
-    rerank::{ReRankRequest, ReRankResponse, ReRankResult},
+    generate::{
+        GenerateApiResponse, GenerateRequest, GenerateStreamRequest, GenerateStreamResponse,
+        Generation,
+    },
+    rerank::{
+        ReRankDocument, ReRankOptions, ReRankRequest, ReRankResponse, ReRankResult, ReRankedItem,
+    },
     tokenize::{TokenizeRequest, TokenizeResponse},
 };
+use async_trait::async_trait;
+use bytes::Bytes;
+use concurrency::{ConcurrencyConfig, ConcurrencyLimiter, ConcurrencyPermit, ConcurrencyStats};
+use middleware::{Middleware, MiddlewareRequest, MiddlewareResponse};
+use rate_limit::{RateLimitConfig, RateLimiter};
 use reqwest::{header, ClientBuilder, StatusCode, Url};
-use tokio::sync::mpsc::{channel, Receiver};
+use telemetry::{RequestTelemetry, ResponseUsage};
+use transport::{ReqwestTransport, ResponseBody, Transport, TransportRequest, TransportResponse};
 
 const COHERE_API_BASE_URL: &str = "https://api.cohere.com";
 const COHERE_API_V1: &str = "v1";
-const COHERE_API_TIMEOUT: Duration = Duration::from_secs(240);
+#[cfg(not(target_arch = "wasm32"))]
+const COHERE_API_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(240);
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 
+pub mod agent;
 pub mod api;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+pub mod concurrency;
+pub mod history;
+pub mod middleware;
+pub mod rate_limit;
+pub mod session;
+mod stream;
+mod telemetry;
+#[cfg(feature = "test-util")]
+pub mod testing;
+#[cfg(all(feature = "local-tokenizer", not(target_arch = "wasm32")))]
+pub mod tokenizer;
+pub mod transport;
+
+pub use stream::CohereStream;
+
+#[cfg(all(feature = "__tls", not(target_arch = "wasm32")))]
+pub use reqwest::Certificate;
 
 #[derive(Error, Debug)]
 pub enum CohereApiError {
@@ -29,22 +61,84 @@ pub enum CohereApiError {
     ApiError(StatusCode, String),
     #[error("API key is invalid")]
     InvalidApiKey,
+    #[error("Request queue is full")]
+    QueueFull,
+    #[error("Unexpected JSON error")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Transport error: {0}")]
+    TransportError(Box<dyn std::error::Error + Send + Sync>),
     #[error("Unknown error")]
     Unknown,
 }
 
+#[derive(Error, Debug, PartialEq)]
+pub enum CohereRequestError {
+    #[error("Missing required field `{0}`")]
+    MissingField(&'static str),
+    #[error("Invalid value for `{0}`: {1}")]
+    InvalidParameter(&'static str, String),
+}
+
 #[derive(Error, Debug)]
 pub enum CohereStreamError {
     #[error("Unexpected deserialization error")]
     RequestError(#[from] serde_json::error::Error),
+    #[error("Failed to read the rest of the stream: {0}")]
+    ReadError(CohereApiError),
     #[error("Unknown error `{0}`")]
     Unknown(String),
 }
 
+impl From<CohereStreamError> for CohereApiError {
+    fn from(error: CohereStreamError) -> Self {
+        match error {
+            CohereStreamError::RequestError(error) => CohereApiError::JsonError(error),
+            CohereStreamError::ReadError(error) => error,
+            CohereStreamError::Unknown(error) => CohereApiError::TransportError(error.into()),
+        }
+    }
+}
+
+/// The Cohere API endpoints supported by the client.
+#[derive(
+    strum_macros::Display, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum Endpoint {
+    CheckApiKey,
+    Generate,
+    Chat,
+    Embed,
+    Classify,
+    Tokenize,
+    Detokenize,
+    Rerank,
+}
+
 /// Cohere Rust SDK to build natural language understanding and generation into your product with a few lines of code.
 pub struct Cohere {
     api_url: String,
-    client: reqwest::Client,
+    headers: header::HeaderMap,
+    transport: Arc<dyn Transport>,
+    rate_limiter: RateLimiter,
+    concurrency_limiter: ConcurrencyLimiter,
+    middleware: Vec<Arc<dyn Middleware>>,
+    // Downloads tokenizers with the client's TLS settings
+    #[cfg(all(feature = "local-tokenizer", not(target_arch = "wasm32")))]
+    http_client: reqwest::Client,
+}
+
+/// Builder to configure a [`Cohere`] client.
+pub struct CohereBuilder {
+    api_url: String,
+    api_key: Option<String>,
+    rate_limits: RateLimitConfig,
+    concurrency: ConcurrencyConfig,
+    middleware: Vec<Arc<dyn Middleware>>,
+    transport: Option<Arc<dyn Transport>>,
+    #[cfg(all(feature = "__tls", not(target_arch = "wasm32")))]
+    root_certificates: Vec<Certificate>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -52,6 +146,8 @@ struct CohereCheckApiKeyResponse {
     valid: bool,
 }
 
+impl ResponseUsage for CohereCheckApiKeyResponse {}
+
 #[derive(Deserialize, Debug)]
 struct CohereApiErrorResponse {
     message: String,
@@ -59,16 +155,79 @@ struct CohereApiErrorResponse {
 
 impl Default for Cohere {
     fn default() -> Self {
-        let api_key = std::env::var("COHERE_API_KEY")
-            .expect("please provide a Cohere API key with the 'COHERE_API_KEY' env variable");
-        Cohere::new(format!("{COHERE_API_BASE_URL}/{COHERE_API_V1}"), api_key)
+        Cohere::builder().build()
     }
 }
 
-impl Cohere {
-    pub fn new<U: Into<String>, K: Into<String>>(api_url: U, api_key: K) -> Self {
-        let api_url: String = api_url.into();
-        let api_key: String = api_key.into();
+impl Default for CohereBuilder {
+    fn default() -> Self {
+        CohereBuilder {
+            api_url: format!("{COHERE_API_BASE_URL}/{COHERE_API_V1}"),
+            api_key: None,
+            rate_limits: RateLimitConfig::default(),
+            concurrency: ConcurrencyConfig::default(),
+            middleware: Vec::new(),
+            transport: None,
+            #[cfg(all(feature = "__tls", not(target_arch = "wasm32")))]
+            root_certificates: Vec::new(),
+        }
+    }
+}
+
+impl CohereBuilder {
+    /// Sets the base URL of the Cohere API. Defaults to the v1 Cohere API.
+    pub fn api_url<U: Into<String>>(mut self, api_url: U) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
+    /// Sets the API key. Defaults to the 'COHERE_API_KEY' env variable.
+    /// Environment variables are not available on wasm so the key must always be set there.
+    pub fn api_key<K: Into<String>>(mut self, api_key: K) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Sets the client-side rate limits. No limits are applied by default.
+    pub fn rate_limits(mut self, rate_limits: RateLimitConfig) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+
+    /// Sets the limits on requests in flight at the same time. No limits are applied by default.
+    pub fn concurrency(mut self, concurrency: ConcurrencyConfig) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Adds middleware to the end of the client's middleware stack.
+    pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Sets the transport used to send requests. Defaults to a [`ReqwestTransport`]
+    /// configured with the client's TLS settings, which are ignored when a transport is set.
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Adds a certificate to the roots trusted by the client, in addition to the roots of the TLS backend.
+    /// This is useful when connecting through a proxy that signs its certificates with an internal CA.
+    #[cfg(all(feature = "__tls", not(target_arch = "wasm32")))]
+    pub fn add_root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /// Builds the client.
+    pub fn build(self) -> Cohere {
+        let api_url = self.api_url;
+        let api_key = self.api_key.unwrap_or_else(|| {
+            std::env::var("COHERE_API_KEY")
+                .expect("please provide a Cohere API key with the 'COHERE_API_KEY' env variable")
+        });
 
         let mut headers = header::HeaderMap::new();
 
@@ -91,100 +250,241 @@ impl Cohere {
             header::HeaderValue::from_static("application/json"),
         );
 
-        let client = ClientBuilder::new()
-            .default_headers(headers)
-            .use_rustls_tls()
-            .timeout(COHERE_API_TIMEOUT)
-            .build()
-            .expect("failed to initialize HTTP client!");
+        let http_client = {
+            let client = ClientBuilder::new();
+            // On wasm requests are made with the runtime's `fetch`, which handles TLS and timeouts itself
+            #[cfg(not(target_arch = "wasm32"))]
+            let client = client.timeout(COHERE_API_TIMEOUT);
+            // rustls is preferred over native-tls when both backends are enabled
+            #[cfg(all(
+                any(
+                    feature = "rustls-tls",
+                    feature = "rustls-tls-webpki-roots",
+                    feature = "rustls-tls-native-roots"
+                ),
+                not(target_arch = "wasm32")
+            ))]
+            let client = client.use_rustls_tls();
+            #[cfg(all(feature = "__tls", not(target_arch = "wasm32")))]
+            let client = self
+                .root_certificates
+                .into_iter()
+                .fold(client, ClientBuilder::add_root_certificate);
+            client.build().expect("failed to initialize HTTP client!")
+        };
+        let transport = self
+            .transport
+            .unwrap_or_else(|| Arc::new(ReqwestTransport::new(http_client.clone())));
+
+        Cohere {
+            api_url,
+            headers,
+            transport,
+            rate_limiter: RateLimiter::new(self.rate_limits),
+            concurrency_limiter: ConcurrencyLimiter::new(self.concurrency),
+            middleware: self.middleware,
+            #[cfg(all(feature = "local-tokenizer", not(target_arch = "wasm32")))]
+            http_client,
+        }
+    }
+}
 
-        Cohere { api_url, client }
+impl Cohere {
+    pub fn new<U: Into<String>, K: Into<String>>(api_url: U, api_key: K) -> Self {
+        Cohere::builder().api_url(api_url).api_key(api_key).build()
     }
 
-    async fn request<Request: Serialize, Response: DeserializeOwned>(
+    /// Returns a builder to configure a client.
+    pub fn builder() -> CohereBuilder {
+        CohereBuilder::default()
+    }
+
+    /// Returns the number of requests currently in flight and queued behind the concurrency limits.
+    pub fn concurrency_stats(&self) -> ConcurrencyStats {
+        self.concurrency_limiter.stats()
+    }
+
+    /// Serializes the request and runs it through the middleware, which may answer it without calling the API.
+    async fn prepare<Request: Serialize>(
         &self,
-        route: &'static str,
+        endpoint: Endpoint,
         payload: Request,
-    ) -> Result<Response, CohereApiError> {
-        let url =
-            Url::parse(&format!("{}/{route}", self.api_url)).expect("api url should be valid");
+    ) -> Result<(MiddlewareRequest, Option<Bytes>), CohereApiError> {
+        let mut request = MiddlewareRequest {
+            endpoint,
+            headers: header::HeaderMap::new(),
+            body: serde_json::to_value(payload)?,
+        };
 
-        let response = self.client.post(url).json(&payload).send().await?;
+        for middleware in &self.middleware {
+            if let Some(body) = middleware.on_request(&mut request).await {
+                return Ok((request, Some(body)));
+            }
+        }
+
+        Ok((request, None))
+    }
+
+    async fn send(
+        &self,
+        request: &MiddlewareRequest,
+    ) -> Result<(ConcurrencyPermit, TransportResponse), CohereApiError> {
+        let url = Url::parse(&format!("{}/{}", self.api_url, request.endpoint))
+            .expect("api url should be valid");
+        // Headers added by middleware take precedence over the client's headers
+        let mut headers = self.headers.clone();
+        headers.extend(request.headers.clone());
+        let body = serde_json::to_vec(&request.body)?.into();
+
+        // Requests held up by the rate limits don't count as in flight
+        self.rate_limiter.acquire(request.endpoint).await;
+        let permit = self.concurrency_limiter.acquire(request.endpoint).await?;
+
+        let response = self
+            .transport
+            .send(TransportRequest {
+                endpoint: request.endpoint,
+                url,
+                headers,
+                body,
+            })
+            .await?;
+
+        self.rate_limiter
+            .observe(request.endpoint, &response.headers);
 
         // Check for any API Warnings
-        if let Some(warning) = response.headers().get("X-API-Warning") {
+        if let Some(warning) = response.headers.get("X-API-Warning") {
             eprintln!("Warning: {:?}", String::from_utf8_lossy(warning.as_bytes()));
         }
 
-        if response.status().is_client_error() || response.status().is_server_error() {
-            Err(self.parse_error(response).await)
-        } else {
-            Ok(response.json::<Response>().await?)
+        Ok((permit, response))
+    }
+
+    async fn on_response(&self, request: &MiddlewareRequest, response: &MiddlewareResponse) {
+        for middleware in self.middleware.iter().rev() {
+            middleware.on_response(request, response).await;
+        }
+    }
+
+    async fn on_error(&self, request: &MiddlewareRequest, error: &CohereApiError) {
+        for middleware in self.middleware.iter().rev() {
+            middleware.on_error(request, error).await;
         }
     }
 
-    async fn request_stream<Request: Serialize, Response: DeserializeOwned + Send + 'static>(
+    async fn request<Request: Serialize, Response: DeserializeOwned + ResponseUsage>(
         &self,
-        route: &'static str,
+        endpoint: Endpoint,
         payload: Request,
-    ) -> Result<Receiver<Result<Response, CohereStreamError>>, CohereApiError> {
-        let url =
-            Url::parse(&format!("{}/{route}", self.api_url)).expect("api url should be valid");
+    ) -> Result<Response, CohereApiError> {
+        let (request, cached) = self.prepare(endpoint, payload).await?;
+        let telemetry = RequestTelemetry::start(&request);
 
-        let mut response = self.client.post(url).json(&payload).send().await?;
+        let result = match cached {
+            Some(body) => serde_json::from_slice(&body).map_err(CohereApiError::from),
+            None => telemetry.instrument(self.fetch(&request, &telemetry)).await,
+        };
+        if let Err(error) = &result {
+            self.on_error(&request, error).await;
+        }
+        telemetry.finish(result.as_ref().err());
+
+        result
+    }
 
-        if response.status().is_client_error() || response.status().is_server_error() {
-            return Err(self.parse_error(response).await);
+    async fn fetch<Response: DeserializeOwned + ResponseUsage>(
+        &self,
+        request: &MiddlewareRequest,
+        telemetry: &RequestTelemetry,
+    ) -> Result<Response, CohereApiError> {
+        let (_permit, response) = self.send(request).await?;
+
+        let TransportResponse {
+            status,
+            headers,
+            body,
+        } = response;
+        let body = body.bytes().await?;
+        telemetry.response(status, &headers);
+
+        let response = MiddlewareResponse {
+            endpoint: request.endpoint,
+            status,
+            headers,
+            body: Some(body.clone()),
+        };
+        self.on_response(request, &response).await;
+
+        if status.is_client_error() || status.is_server_error() {
+            Err(parse_error(status, &body))
+        } else {
+            let response = serde_json::from_slice(&body)?;
+            telemetry.usage(&response);
+            Ok(response)
         }
+    }
 
-        let (tx, rx) = channel::<Result<Response, CohereStreamError>>(1);
-        tokio::spawn(async move {
-            let mut buf = bytes::BytesMut::with_capacity(1024);
-            while let Ok(Some(chunk)) = response.chunk().await {
-                if chunk.is_empty() {
-                    break;
-                }
-                buf.extend_from_slice(&chunk);
-                if !chunk.ends_with(b"\n") {
-                    continue;
-                }
-                match serde_json::from_slice::<Response>(&buf) {
-                    Ok(v) => tx
-                        .send(Ok(v))
-                        .await
-                        .expect("Failed to send message to channel"),
-                    Err(e) => tx
-                        .send(Err(CohereStreamError::from(e)))
-                        .await
-                        .expect("Failed to send error to channel"),
-                }
-                buf.clear()
+    async fn request_stream<Request: Serialize, Response: DeserializeOwned>(
+        &self,
+        endpoint: Endpoint,
+        payload: Request,
+    ) -> Result<CohereStream<Response>, CohereApiError> {
+        let (request, cached) = self.prepare(endpoint, payload).await?;
+        let telemetry = RequestTelemetry::start(&request);
+
+        let result = match cached {
+            Some(body) => Ok((None, ResponseBody::from(body))),
+            None => {
+                telemetry
+                    .instrument(self.fetch_stream(&request, &telemetry))
+                    .await
             }
-        });
+        };
+        // The response body is not shared between threads, so it can't be borrowed while waiting on middleware
+        let (permit, body) = match result {
+            Ok(stream) => stream,
+            Err(error) => {
+                self.on_error(&request, &error).await;
+                telemetry.finish(Some(&error));
+                return Err(error);
+            }
+        };
+        Ok(CohereStream::new(body, permit, telemetry))
+    }
 
-        Ok(rx)
-    }
-
-    async fn parse_error(&self, response: reqwest::Response) -> CohereApiError {
-        let status = response.status();
-        let text = response.text().await;
-        match text {
-            Err(_) => CohereApiError::Unknown,
-            Ok(text) => CohereApiError::ApiError(
-                status,
-                serde_json::from_str::<CohereApiErrorResponse>(&text)
-                    .unwrap_or(CohereApiErrorResponse {
-                        message: format!("Unknown API Error: {}", text),
-                    })
-                    .message,
-            ),
+    async fn fetch_stream(
+        &self,
+        request: &MiddlewareRequest,
+        telemetry: &RequestTelemetry,
+    ) -> Result<(Option<ConcurrencyPermit>, ResponseBody), CohereApiError> {
+        let (permit, response) = self.send(request).await?;
+
+        let status = response.status;
+        let mut middleware_response = MiddlewareResponse {
+            endpoint: request.endpoint,
+            status,
+            headers: response.headers,
+            body: None,
+        };
+
+        if status.is_client_error() || status.is_server_error() {
+            let body = response.body.bytes().await?;
+            telemetry.response(status, &middleware_response.headers);
+            middleware_response.body = Some(body.clone());
+            self.on_response(request, &middleware_response).await;
+            return Err(parse_error(status, &body));
         }
+        telemetry.response(status, &middleware_response.headers);
+        self.on_response(request, &middleware_response).await;
+
+        Ok((Some(permit), response.body))
     }
 
     /// Verify that the Cohere API key being used is valid
     pub async fn check_api_key(&self) -> Result<(), CohereApiError> {
         let response = self
-            .request::<(), CohereCheckApiKeyResponse>("check-api-key", ())
+            .request::<(), CohereCheckApiKeyResponse>(Endpoint::CheckApiKey, ())
             .await?;
 
         match response.valid {
@@ -199,23 +499,39 @@ impl Cohere {
         request: &GenerateRequest<'input>,
     ) -> Result<Vec<Generation>, CohereApiError> {
         let response = self
-            .request::<_, GenerateResponse>("generate", request)
+            .request::<_, GenerateApiResponse>(Endpoint::Generate, request)
             .await?;
 
         Ok(response.generations)
     }
 
+    /// Generates realistic text conditioned on a given input, streaming the generated text as it is produced.
+    pub async fn generate_stream<'input>(
+        &self,
+        request: &GenerateRequest<'input>,
+    ) -> Result<CohereStream<GenerateStreamResponse>, CohereApiError> {
+        let stream_request = GenerateStreamRequest {
+            request,
+            stream: true,
+        };
+        let response = self
+            .request_stream::<_, GenerateStreamResponse>(Endpoint::Generate, stream_request)
+            .await?;
+
+        Ok(response)
+    }
+
     /// Chat with Cohere's LLM
     pub async fn chat<'input>(
         &self,
         request: &ChatRequest<'input>,
-    ) -> Result<Receiver<Result<ChatStreamResponse, CohereStreamError>>, CohereApiError> {
+    ) -> Result<CohereStream<ChatStreamResponse>, CohereApiError> {
         let stream_request = ChatStreamRequest {
             request,
             stream: true,
         };
         let response = self
-            .request_stream::<_, ChatStreamResponse>("chat", stream_request)
+            .request_stream::<_, ChatStreamResponse>(Endpoint::Chat, stream_request)
             .await?;
 
         Ok(response)
@@ -228,7 +544,9 @@ impl Cohere {
         &self,
         request: &EmbedRequest<'input>,
     ) -> Result<Vec<Vec<f64>>, CohereApiError> {
-        let response = self.request::<_, EmbedResponse>("embed", request).await?;
+        let response = self
+            .request::<_, EmbedResponse>(Endpoint::Embed, request)
+            .await?;
 
         Ok(response.embeddings)
     }
@@ -240,7 +558,7 @@ impl Cohere {
         request: &ClassifyRequest<'input>,
     ) -> Result<Vec<Classification>, CohereApiError> {
         let response = self
-            .request::<_, ClassifyResponse>("classify", request)
+            .request::<_, ClassifyResponse>(Endpoint::Classify, request)
             .await?;
 
         Ok(response.classifications)
@@ -251,7 +569,7 @@ impl Cohere {
         &self,
         request: &TokenizeRequest<'input>,
     ) -> Result<TokenizeResponse, CohereApiError> {
-        let response = self.request("tokenize", request).await?;
+        let response = self.request(Endpoint::Tokenize, request).await?;
 
         Ok(response)
     }
@@ -262,7 +580,7 @@ impl Cohere {
         request: &DetokenizeRequest<'input>,
     ) -> Result<String, CohereApiError> {
         let response = self
-            .request::<_, DetokenizeResponse>("detokenize", request)
+            .request::<_, DetokenizeResponse>(Endpoint::Detokenize, request)
             .await?;
 
         Ok(response.text)
@@ -273,8 +591,196 @@ impl Cohere {
         &self,
         request: &ReRankRequest<'input>,
     ) -> Result<Vec<ReRankResult>, CohereApiError> {
-        let response = self.request::<_, ReRankResponse>("rerank", request).await?;
+        let response = self
+            .request::<_, ReRankResponse>(Endpoint::Rerank, request)
+            .await?;
 
         Ok(response.results)
     }
+
+    /// Reranks items such as structs, returning them from most to least relevant with their relevance scores.
+    /// The items are ranked as the documents of a request with the options, and must serialize to strings or objects.
+    /// A result that doesn't rank exactly one of the items fails with `CohereApiError::TransportError`.
+    pub async fn rerank_items<'input, T: Serialize + Send>(
+        &self,
+        options: &ReRankOptions<'input>,
+        items: Vec<T>,
+    ) -> Result<Vec<ReRankedItem<T>>, CohereApiError> {
+        CohereApi::rerank_items(self, options, items).await
+    }
+}
+
+/// The operations of the Cohere API, implemented by [`Cohere`].
+///
+/// Code that depends on this trait rather than on [`Cohere`] can be tested with a fake
+/// implementation such as `cohere_rust::testing::MockCohere`.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait CohereApi: Send + Sync {
+    /// Verify that the Cohere API key being used is valid
+    async fn check_api_key(&self) -> Result<(), CohereApiError>;
+
+    /// Generates realistic text conditioned on a given input.
+    async fn generate<'input>(
+        &self,
+        request: &GenerateRequest<'input>,
+    ) -> Result<Vec<Generation>, CohereApiError>;
+
+    /// Generates realistic text conditioned on a given input, streaming the generated text as it is produced.
+    async fn generate_stream<'input>(
+        &self,
+        request: &GenerateRequest<'input>,
+    ) -> Result<CohereStream<GenerateStreamResponse>, CohereApiError>;
+
+    /// Chat with Cohere's LLM
+    async fn chat<'input>(
+        &self,
+        request: &ChatRequest<'input>,
+    ) -> Result<CohereStream<ChatStreamResponse>, CohereApiError>;
+
+    /// Returns text embeddings.
+    async fn embed<'input>(
+        &self,
+        request: &EmbedRequest<'input>,
+    ) -> Result<Vec<Vec<f64>>, CohereApiError>;
+
+    /// Makes a prediction about which label fits the specified text inputs best.
+    async fn classify<'input>(
+        &self,
+        request: &ClassifyRequest<'input>,
+    ) -> Result<Vec<Classification>, CohereApiError>;
+
+    /// Splits input text into smaller units called tokens using byte-pair encoding (BPE).
+    async fn tokenize<'input>(
+        &self,
+        request: &TokenizeRequest<'input>,
+    ) -> Result<TokenizeResponse, CohereApiError>;
+
+    /// Takes tokens using byte-pair encoding and returns their text representation.
+    async fn detokenize<'input>(
+        &self,
+        request: &DetokenizeRequest<'input>,
+    ) -> Result<String, CohereApiError>;
+
+    /// Takes a query plus an list of texts and return an ordered array with each text assigned a relevance score.
+    async fn rerank<'input>(
+        &self,
+        request: &ReRankRequest<'input>,
+    ) -> Result<Vec<ReRankResult>, CohereApiError>;
+
+    /// Reranks items such as structs, returning them from most to least relevant with their relevance scores.
+    /// The items are ranked as the documents of a request with the options, and must serialize to strings or objects.
+    /// A result that doesn't rank exactly one of the items fails with `CohereApiError::TransportError`.
+    async fn rerank_items<'input, T: Serialize + Send>(
+        &self,
+        options: &ReRankOptions<'input>,
+        items: Vec<T>,
+    ) -> Result<Vec<ReRankedItem<T>>, CohereApiError>
+    where
+        Self: Sized,
+    {
+        let documents = items
+            .iter()
+            .map(ReRankDocument::from_serialize)
+            .collect::<Result<Vec<_>, _>>()?;
+        let results = self.rerank(&options.request(&documents)).await?;
+
+        let mut items: Vec<Option<T>> = items.into_iter().map(Some).collect();
+        results
+            .into_iter()
+            .map(|result| {
+                let item = items
+                    .get_mut(result.index as usize)
+                    .and_then(Option::take)
+                    .ok_or_else(|| {
+                        CohereApiError::TransportError(
+                            format!(
+                                "the API ranked document {}, which wasn't sent or was already ranked",
+                                result.index
+                            )
+                            .into(),
+                        )
+                    })?;
+                Ok(ReRankedItem {
+                    item,
+                    relevance_score: result.relevance_score,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl CohereApi for Cohere {
+    async fn check_api_key(&self) -> Result<(), CohereApiError> {
+        Cohere::check_api_key(self).await
+    }
+
+    async fn generate<'input>(
+        &self,
+        request: &GenerateRequest<'input>,
+    ) -> Result<Vec<Generation>, CohereApiError> {
+        Cohere::generate(self, request).await
+    }
+
+    async fn generate_stream<'input>(
+        &self,
+        request: &GenerateRequest<'input>,
+    ) -> Result<CohereStream<GenerateStreamResponse>, CohereApiError> {
+        Cohere::generate_stream(self, request).await
+    }
+
+    async fn chat<'input>(
+        &self,
+        request: &ChatRequest<'input>,
+    ) -> Result<CohereStream<ChatStreamResponse>, CohereApiError> {
+        Cohere::chat(self, request).await
+    }
+
+    async fn embed<'input>(
+        &self,
+        request: &EmbedRequest<'input>,
+    ) -> Result<Vec<Vec<f64>>, CohereApiError> {
+        Cohere::embed(self, request).await
+    }
+
+    async fn classify<'input>(
+        &self,
+        request: &ClassifyRequest<'input>,
+    ) -> Result<Vec<Classification>, CohereApiError> {
+        Cohere::classify(self, request).await
+    }
+
+    async fn tokenize<'input>(
+        &self,
+        request: &TokenizeRequest<'input>,
+    ) -> Result<TokenizeResponse, CohereApiError> {
+        Cohere::tokenize(self, request).await
+    }
+
+    async fn detokenize<'input>(
+        &self,
+        request: &DetokenizeRequest<'input>,
+    ) -> Result<String, CohereApiError> {
+        Cohere::detokenize(self, request).await
+    }
+
+    async fn rerank<'input>(
+        &self,
+        request: &ReRankRequest<'input>,
+    ) -> Result<Vec<ReRankResult>, CohereApiError> {
+        Cohere::rerank(self, request).await
+    }
+}
+
+fn parse_error(status: StatusCode, body: &[u8]) -> CohereApiError {
+    CohereApiError::ApiError(
+        status,
+        serde_json::from_slice::<CohereApiErrorResponse>(body)
+            .unwrap_or(CohereApiErrorResponse {
+                message: format!("Unknown API Error: {}", String::from_utf8_lossy(body)),
+            })
+            .message,
+    )
 }
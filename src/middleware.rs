@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use reqwest::{header::HeaderMap, StatusCode};
+
+use crate::{CohereApiError, Endpoint};
+
+/// An outgoing request as seen by middleware.
+#[derive(Clone, Debug)]
+pub struct MiddlewareRequest {
+    /// The endpoint the request is sent to.
+    pub endpoint: Endpoint,
+    /// Headers sent in addition to the client's default headers.
+    pub headers: HeaderMap,
+    /// The JSON body of the request.
+    pub body: serde_json::Value,
+}
+
+/// A response received from the API as seen by middleware.
+#[derive(Clone, Debug)]
+pub struct MiddlewareResponse {
+    /// The endpoint the request was sent to.
+    pub endpoint: Endpoint,
+    /// The status code of the response.
+    pub status: StatusCode,
+    /// The headers of the response.
+    pub headers: HeaderMap,
+    /// The body of the response. Streamed responses are handed to the caller as they
+    /// arrive so their body is not available to middleware.
+    pub body: Option<Bytes>,
+}
+
+/// Hooks that run around every request the client makes.
+///
+/// Middleware is called in the order it was added to the client before a request is sent,
+/// and in reverse order once a response or an error comes back.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait Middleware: Send + Sync {
+    /// Called before the request is sent, and may change its headers and body.
+    /// Returning a body skips the remaining middleware and the API call and uses the body as
+    /// a successful response, which allows middleware to serve responses from a cache.
+    async fn on_request(&self, _request: &mut MiddlewareRequest) -> Option<Bytes> {
+        None
+    }
+
+    /// Called for every response received from the API, including unsuccessful ones.
+    async fn on_response(&self, _request: &MiddlewareRequest, _response: &MiddlewareResponse) {}
+
+    /// Called when the request fails.
+    async fn on_error(&self, _request: &MiddlewareRequest, _error: &CohereApiError) {}
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl<M: Middleware + ?Sized> Middleware for Arc<M> {
+    async fn on_request(&self, request: &mut MiddlewareRequest) -> Option<Bytes> {
+        (**self).on_request(request).await
+    }
+
+    async fn on_response(&self, request: &MiddlewareRequest, response: &MiddlewareResponse) {
+        (**self).on_response(request, response).await
+    }
+
+    async fn on_error(&self, request: &MiddlewareRequest, error: &CohereApiError) {
+        (**self).on_error(request, error).await
+    }
+}
@@ -0,0 +1,271 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    std::time::{Instant, SystemTime, UNIX_EPOCH},
+    tokio::time::sleep,
+};
+// Neither the standard library clock nor tokio timers are available in browsers and workers
+#[cfg(target_arch = "wasm32")]
+use wasmtimer::{
+    std::{Instant, SystemTime, UNIX_EPOCH},
+    tokio::sleep,
+};
+
+use crate::Endpoint;
+
+/// A number of requests allowed over a period of time.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    /// The number of requests allowed per period. This is also the maximum burst size.
+    pub requests: u32,
+    /// The period over which the requests are allowed.
+    pub period: Duration,
+}
+
+impl RateLimit {
+    pub fn per_second(requests: u32) -> Self {
+        RateLimit {
+            requests,
+            period: Duration::from_secs(1),
+        }
+    }
+
+    pub fn per_minute(requests: u32) -> Self {
+        RateLimit {
+            requests,
+            period: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Client-side rate limits applied before a request is sent.
+/// Requests that exceed a limit are queued until the limit allows them rather than failing.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RateLimitConfig {
+    /// optional - A limit shared by every request made by the client.
+    #[serde(default)]
+    pub client: Option<RateLimit>,
+    /// optional - Limits that only apply to requests made to a specific endpoint.
+    #[serde(default)]
+    pub endpoints: HashMap<Endpoint, RateLimit>,
+    /// When enabled, `Retry-After` and `X-RateLimit-*` response headers pause the limits
+    /// until the API is ready to accept requests again. Requests to an endpoint without a limit
+    /// are paused too, even if no limits are configured.
+    #[serde(default)]
+    pub adaptive: bool,
+}
+
+pub(crate) struct RateLimiter {
+    client: Option<TokenBucket>,
+    endpoints: HashMap<Endpoint, TokenBucket>,
+    adaptive: bool,
+    /// The pauses requested by the API for endpoints without a limit.
+    pauses: Mutex<HashMap<Endpoint, Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            client: config.client.map(TokenBucket::new),
+            endpoints: config
+                .endpoints
+                .into_iter()
+                .map(|(endpoint, limit)| (endpoint, TokenBucket::new(limit)))
+                .collect(),
+            adaptive: config.adaptive,
+            pauses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until both the client and endpoint limits allow another request.
+    pub async fn acquire(&self, endpoint: Endpoint) {
+        while let Some(wait) = self.pause_time(endpoint) {
+            sleep(wait).await;
+        }
+
+        let endpoint = self.endpoints.get(&endpoint);
+        // The endpoint's token is only taken once the client limit allows the request too, and no other
+        // request can take it in the meantime, so that waiting on the endpoint limit doesn't hold up
+        // requests to other endpoints that share the client limit
+        let _turn = match endpoint {
+            Some(bucket) => Some(bucket.ready().await),
+            None => None,
+        };
+        if let Some(bucket) = &self.client {
+            bucket.acquire().await;
+        }
+        if let Some(bucket) = endpoint {
+            bucket.take();
+        }
+    }
+
+    /// Pauses the limits for an endpoint when the API signals that it is rate limiting requests.
+    pub fn observe(&self, endpoint: Endpoint, headers: &HeaderMap) {
+        if !self.adaptive {
+            return;
+        }
+        if let Some(delay) = retry_delay(headers) {
+            let until = Instant::now() + delay;
+            if let Some(bucket) = &self.client {
+                bucket.pause_until(until);
+            }
+            match self.endpoints.get(&endpoint) {
+                Some(bucket) => bucket.pause_until(until),
+                None => {
+                    let mut pauses = self.pauses.lock().expect("rate limit pauses lock poisoned");
+                    let paused = pauses.entry(endpoint).or_insert(until);
+                    *paused = (*paused).max(until);
+                }
+            }
+        }
+    }
+
+    /// Returns how long requests to an endpoint without a limit are paused for, if they are.
+    fn pause_time(&self, endpoint: Endpoint) -> Option<Duration> {
+        let mut pauses = self.pauses.lock().expect("rate limit pauses lock poisoned");
+        let until = *pauses.get(&endpoint)?;
+        let now = Instant::now();
+        if until > now {
+            return Some(until - now);
+        }
+        pauses.remove(&endpoint);
+        None
+    }
+}
+
+/// Reads how long to wait before the next request from the response headers, if the API asked to wait.
+fn retry_delay(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = |name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .filter(|value| value.is_finite() && *value >= 0.0)
+            .map(Duration::from_secs_f64)
+    };
+
+    if let Some(delay) = seconds(RETRY_AFTER.as_str()) {
+        return Some(delay);
+    }
+    // `Retry-After` can also be the date after which to retry
+    let retry_date = headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value.trim()).ok());
+    if let Some(date) = retry_date {
+        let date = date.duration_since(std::time::UNIX_EPOCH).ok()?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        return Some(date.saturating_sub(now));
+    }
+
+    let remaining = headers
+        .get("X-RateLimit-Remaining")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok());
+    match remaining {
+        Some(0) => seconds("X-RateLimit-Reset"),
+        _ => None,
+    }
+}
+
+struct TokenBucket {
+    limit: RateLimit,
+    // Waiters hold this lock while sleeping so requests are let through in the order they arrived
+    queue: tokio::sync::Mutex<()>,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit) -> Self {
+        let limit = RateLimit {
+            requests: limit.requests.max(1),
+            period: limit.period.max(Duration::from_millis(1)),
+        };
+        TokenBucket {
+            limit,
+            queue: tokio::sync::Mutex::new(()),
+            state: Mutex::new(BucketState {
+                tokens: limit.requests as f64,
+                last_refill: Instant::now(),
+                paused_until: None,
+            }),
+        }
+    }
+
+    async fn acquire(&self) {
+        let _turn = self.ready().await;
+        self.take();
+    }
+
+    /// Waits until a token is available, returning the turn that keeps other requests from taking it.
+    async fn ready(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        let turn = self.queue.lock().await;
+        while let Some(wait) = self.wait_time() {
+            sleep(wait).await;
+        }
+        turn
+    }
+
+    /// Returns how long to wait for the next token, or `None` if one is available.
+    fn wait_time(&self) -> Option<Duration> {
+        let mut state = self.state.lock().expect("rate limit state lock poisoned");
+        let now = Instant::now();
+
+        if let Some(until) = state.paused_until {
+            if until > now {
+                return Some(until - now);
+            }
+            state.paused_until = None;
+        }
+
+        self.refill(&mut state, now);
+        if state.tokens >= 1.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - state.tokens) / self.rate()))
+        }
+    }
+
+    /// Takes a token, which the caller has waited for with `ready`.
+    fn take(&self) {
+        let mut state = self.state.lock().expect("rate limit state lock poisoned");
+        self.refill(&mut state, Instant::now());
+        state.tokens -= 1.0;
+    }
+
+    fn refill(&self, state: &mut BucketState, now: Instant) {
+        // The bucket doesn't refill before the end of a pause
+        if let Some(elapsed) = now.checked_duration_since(state.last_refill) {
+            state.tokens = (state.tokens + elapsed.as_secs_f64() * self.rate())
+                .min(self.limit.requests as f64);
+            state.last_refill = now;
+        }
+    }
+
+    /// The number of tokens added per second.
+    fn rate(&self) -> f64 {
+        self.limit.requests as f64 / self.limit.period.as_secs_f64()
+    }
+
+    fn pause_until(&self, until: Instant) {
+        let mut state = self.state.lock().expect("rate limit state lock poisoned");
+        let until = state.paused_until.map_or(until, |paused| paused.max(until));
+        state.paused_until = Some(until);
+        // Nothing is left in the bucket once the API starts rejecting requests
+        // and it only starts refilling after the pause
+        state.tokens = 0.0;
+        state.last_refill = until;
+    }
+}
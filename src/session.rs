@@ -0,0 +1,214 @@
+//! Conversations with the chat endpoint that keep track of their own history.
+
+use std::{
+    future::poll_fn,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures_core::Stream;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::chat::{ChatMessage, ChatRequest, ChatResponse, ChatStreamResponse, ToolCall, ToolResult},
+    history::HistoryTruncation,
+    CohereApi, CohereApiError, CohereStream, CohereStreamError,
+};
+
+/// A conversation with the chat endpoint that appends every exchange to its history.
+///
+/// Each turn sends the session's settings with the history so far, and the user's message and the model's
+/// reply, including any tool calls, are appended once the reply is complete. A turn that fails isn't
+/// appended, so it can be retried. The session can be serialized to resume the conversation later.
+///
+/// The history is sent in full unless [`ChatSession::truncation`] is set to keep it under a token budget.
+///
+/// ```no_run
+/// # use cohere_rust::{api::chat::ChatRequest, session::ChatSession, Cohere};
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Cohere::default();
+/// let mut session = ChatSession::new(ChatRequest {
+///     preamble: Some("Reply as if you were a Shakespearean actor".to_string()),
+///     ..Default::default()
+/// });
+///
+/// let reply = session.send(&client, "Hello! Tell me about Cohere.").await?;
+/// println!("{}", reply.text);
+///
+/// let mut stream = session.send_stream(&client, "Tell me more.").await?;
+/// while let Some(message) = stream.recv().await {
+///     println!("{:?}", message?);
+/// }
+/// assert_eq!(4, session.history.len());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ChatSession {
+    /// The parameters sent with every turn, such as the model, preamble and tools.
+    /// The message, chat history and tool results are set by the session.
+    pub settings: ChatRequest<'static>,
+    /// The messages exchanged so far, oldest first.
+    pub history: Vec<ChatMessage>,
+    /// Truncates the history before each turn to keep the request under a token budget.
+    /// The history is left to the API to truncate if this isn't set.
+    #[serde(default)]
+    pub truncation: Option<HistoryTruncation>,
+}
+
+impl ChatSession {
+    pub fn new(settings: ChatRequest<'static>) -> Self {
+        ChatSession {
+            settings,
+            history: Vec::new(),
+            truncation: None,
+        }
+    }
+
+    /// Sends a message and waits for the whole reply.
+    pub async fn send<C: CohereApi + ?Sized>(
+        &mut self,
+        client: &C,
+        message: impl Into<String>,
+    ) -> Result<ChatResponse, CohereApiError> {
+        self.send_stream(client, message).await?.response().await
+    }
+
+    /// Sends a message and streams the reply, which is appended to the history once the stream ends.
+    pub async fn send_stream<C: CohereApi + ?Sized>(
+        &mut self,
+        client: &C,
+        message: impl Into<String>,
+    ) -> Result<ChatTurnStream<'_>, CohereApiError> {
+        let message = message.into();
+        let turn = ChatMessage::User {
+            message: message.clone(),
+        };
+        self.start(client, message, Vec::new(), turn).await
+    }
+
+    /// Sends the results of the tool calls requested by the last reply and waits for the whole reply.
+    pub async fn send_tool_results<C: CohereApi + ?Sized>(
+        &mut self,
+        client: &C,
+        tool_results: Vec<ToolResult>,
+    ) -> Result<ChatResponse, CohereApiError> {
+        self.send_tool_results_stream(client, tool_results)
+            .await?
+            .response()
+            .await
+    }
+
+    /// Sends the results of the tool calls requested by the last reply and streams the reply.
+    pub async fn send_tool_results_stream<C: CohereApi + ?Sized>(
+        &mut self,
+        client: &C,
+        tool_results: Vec<ToolResult>,
+    ) -> Result<ChatTurnStream<'_>, CohereApiError> {
+        let turn = ChatMessage::Tool {
+            tool_results: tool_results.clone(),
+        };
+        self.start(client, String::new(), tool_results, turn).await
+    }
+
+    /// The tool calls requested by the last reply, which are answered with [`ChatSession::send_tool_results`].
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        match self.history.last() {
+            Some(ChatMessage::Chatbot { tool_calls, .. }) => tool_calls,
+            _ => &[],
+        }
+    }
+
+    async fn start<C: CohereApi + ?Sized>(
+        &mut self,
+        client: &C,
+        message: String,
+        tool_results: Vec<ToolResult>,
+        turn: ChatMessage,
+    ) -> Result<ChatTurnStream<'_>, CohereApiError> {
+        let mut request = ChatRequest {
+            message: message.into(),
+            chat_history: None,
+            tool_results: (!tool_results.is_empty()).then(|| tool_results.into()),
+            ..self.settings.clone()
+        };
+        // A copy of the history is truncated, which replaces the history only once the turn has succeeded
+        let truncated = match &self.truncation {
+            Some(truncation) => {
+                let mut history = self.history.clone();
+                truncation.truncate(client, &request, &mut history).await?;
+                Some(history)
+            }
+            None => None,
+        };
+        let history = truncated.as_deref().unwrap_or(&self.history);
+        request.chat_history = (!history.is_empty()).then(|| history.into());
+        let stream = client.chat(&request).await?;
+
+        Ok(ChatTurnStream {
+            session: self,
+            stream,
+            turn: Some(turn),
+            truncated,
+        })
+    }
+}
+
+/// The streamed reply to a turn of a [`ChatSession`].
+///
+/// The turn is appended to the session's history when the `stream-end` message is received, along with any
+/// truncation of the history, so a stream that is dropped before the end of the reply leaves the history unchanged.
+pub struct ChatTurnStream<'session> {
+    session: &'session mut ChatSession,
+    stream: CohereStream<ChatStreamResponse>,
+    turn: Option<ChatMessage>,
+    truncated: Option<Vec<ChatMessage>>,
+}
+
+impl ChatTurnStream<'_> {
+    /// Receives the next message of the reply, or `None` once the reply has ended.
+    pub async fn recv(&mut self) -> Option<Result<ChatStreamResponse, CohereStreamError>> {
+        poll_fn(|cx| self.poll_message(cx)).await
+    }
+
+    /// Reads the rest of the reply and returns the complete response.
+    pub async fn response(mut self) -> Result<ChatResponse, CohereApiError> {
+        while let Some(message) = self.recv().await {
+            if let ChatStreamResponse::ChatStreamEnd { response, .. } = message? {
+                return Ok(response);
+            }
+        }
+        Err(CohereApiError::TransportError(
+            "the chat stream ended before the reply was complete".into(),
+        ))
+    }
+
+    fn poll_message(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<ChatStreamResponse, CohereStreamError>>> {
+        let message = ready!(Pin::new(&mut self.stream).poll_next(cx));
+        if let Some(Ok(ChatStreamResponse::ChatStreamEnd { response, .. })) = &message {
+            if let Some(turn) = self.turn.take() {
+                if let Some(history) = self.truncated.take() {
+                    self.session.history = history;
+                }
+                self.session.history.push(turn);
+                self.session.history.push(ChatMessage::Chatbot {
+                    message: response.text.clone(),
+                    tool_calls: response.tool_calls.clone(),
+                });
+            }
+        }
+        Poll::Ready(message)
+    }
+}
+
+impl Stream for ChatTurnStream<'_> {
+    type Item = Result<ChatStreamResponse, CohereStreamError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_message(cx)
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use bytes::BytesMut;
+use futures_core::Stream;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    concurrency::ConcurrencyPermit, telemetry::RequestTelemetry, transport::ResponseBody,
+    CohereStreamError,
+};
+
+/// The messages of a streamed response.
+///
+/// The response body is only read from the API while the stream is polled, so it works with any
+/// async executor and doesn't spawn a background task.
+pub struct CohereStream<T> {
+    body: ResponseBody,
+    buf: BytesMut,
+    // The request stays in flight until the whole stream has been read or the stream is dropped
+    permit: Option<ConcurrencyPermit>,
+    telemetry: Option<RequestTelemetry>,
+    // Messages that are already available, which are received before the body is read
+    messages: VecDeque<T>,
+}
+
+impl<T: DeserializeOwned> CohereStream<T> {
+    pub(crate) fn new(
+        body: ResponseBody,
+        permit: Option<ConcurrencyPermit>,
+        telemetry: RequestTelemetry,
+    ) -> Self {
+        telemetry.stream_started();
+        CohereStream {
+            body,
+            buf: BytesMut::with_capacity(1024),
+            permit,
+            telemetry: Some(telemetry),
+            messages: VecDeque::new(),
+        }
+    }
+
+    /// A stream of messages that are already available, for example to return from a fake client in tests.
+    pub fn from_messages(messages: impl IntoIterator<Item = T>) -> Self {
+        CohereStream {
+            body: ResponseBody::from(Vec::new()),
+            buf: BytesMut::new(),
+            permit: None,
+            telemetry: None,
+            messages: messages.into_iter().collect(),
+        }
+    }
+
+    /// Receives the next message of the stream, or `None` once the stream has ended.
+    pub async fn recv(&mut self) -> Option<Result<T, CohereStreamError>> {
+        poll_fn(|cx| self.poll_message(cx)).await
+    }
+
+    fn poll_message(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, CohereStreamError>>> {
+        if let Some(message) = self.messages.pop_front() {
+            return Poll::Ready(Some(Ok(message)));
+        }
+        loop {
+            // Each streamed message is a line of JSON, and chunks may split or join lines
+            if let Some(end) = self.buf.iter().position(|b| *b == b'\n') {
+                let line = self.buf.split_to(end + 1).freeze();
+                if let Some(message) = self.parse_message(&line) {
+                    return Poll::Ready(Some(message));
+                }
+                continue;
+            }
+            if self.telemetry.is_none() {
+                return Poll::Ready(None);
+            }
+
+            match ready!(Pin::new(&mut self.body).poll_next(cx)) {
+                Some(Ok(chunk)) => self.buf.extend_from_slice(&chunk),
+                // A failure to read the rest of the body ends the stream with an error, discarding any partial message
+                Some(Err(error)) => {
+                    self.buf.clear();
+                    self.permit = None;
+                    if let Some(telemetry) = self.telemetry.take() {
+                        telemetry.finish(Some(&error));
+                    }
+                    return Poll::Ready(Some(Err(CohereStreamError::ReadError(error))));
+                }
+                None => {
+                    let line = self.buf.split().freeze();
+                    let message = self.parse_message(&line);
+                    self.finish();
+                    if message.is_some() {
+                        return Poll::Ready(message);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parses a line of the streamed response, skipping blank lines.
+    fn parse_message(&mut self, line: &[u8]) -> Option<Result<T, CohereStreamError>> {
+        if line.trim_ascii().is_empty() {
+            return None;
+        }
+        if let Some(telemetry) = &mut self.telemetry {
+            telemetry.stream_message(line);
+        }
+        Some(serde_json::from_slice::<T>(line).map_err(CohereStreamError::from))
+    }
+}
+
+impl<T> CohereStream<T> {
+    fn finish(&mut self) {
+        self.permit = None;
+        if let Some(telemetry) = self.telemetry.take() {
+            telemetry.stream_finished();
+        }
+    }
+}
+
+// Messages are never pinned, so the stream can be moved regardless of the message type
+impl<T> Unpin for CohereStream<T> {}
+
+impl<T: DeserializeOwned> Stream for CohereStream<T> {
+    type Item = Result<T, CohereStreamError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_message(cx)
+    }
+}
+
+impl<T> Drop for CohereStream<T> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
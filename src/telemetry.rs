@@ -0,0 +1,318 @@
+//! Instrumentation for API calls. Every hook is a no-op unless the `tracing` or `metrics` feature is enabled.
+#![cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+
+use std::future::Future;
+
+use reqwest::{header::HeaderMap, StatusCode};
+use serde::Deserialize;
+
+use crate::{middleware::MiddlewareRequest, CohereApiError};
+
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+use usage::StreamEvent;
+
+#[cfg(all(
+    any(feature = "tracing", feature = "metrics"),
+    not(target_arch = "wasm32")
+))]
+use std::time::Instant;
+#[cfg(all(any(feature = "tracing", feature = "metrics"), target_arch = "wasm32"))]
+use wasmtimer::std::Instant;
+
+/// The parts of a response that describe the API call itself rather than its result.
+///
+/// They are read from the response once it has been deserialized, so that its body is only parsed once.
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+pub(crate) trait ResponseUsage {
+    fn id(&self) -> Option<&str> {
+        None
+    }
+
+    fn meta(&self) -> Option<&ResponseMeta> {
+        None
+    }
+}
+
+/// The `meta` object of a response.
+#[derive(Deserialize, Debug, Default)]
+#[cfg_attr(not(any(feature = "tracing", feature = "metrics")), allow(dead_code))]
+pub(crate) struct ResponseMeta {
+    #[serde(default)]
+    billed_units: Option<BilledUnits>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[cfg_attr(not(any(feature = "tracing", feature = "metrics")), allow(dead_code))]
+struct BilledUnits {
+    #[serde(default)]
+    input_tokens: Option<f64>,
+    #[serde(default)]
+    output_tokens: Option<f64>,
+}
+
+/// Instruments a single API call from the moment it is sent until its response has been read.
+pub(crate) struct RequestTelemetry {
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "metrics")]
+    labels: [(&'static str, String); 2],
+    #[cfg(feature = "metrics")]
+    first_token_received: bool,
+}
+
+impl RequestTelemetry {
+    pub fn start(request: &MiddlewareRequest) -> Self {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let model = request.body.get("model").and_then(|model| model.as_str());
+
+        RequestTelemetry {
+            #[cfg(any(feature = "tracing", feature = "metrics"))]
+            start: Instant::now(),
+            #[cfg(feature = "tracing")]
+            span: {
+                let operation = usage::operation_name(request.endpoint);
+                tracing::info_span!(
+                    "cohere",
+                    otel.name = %match model {
+                        Some(model) => format!("{operation} {model}"),
+                        None => operation.to_string(),
+                    },
+                    otel.kind = "client",
+                    otel.status_code = tracing::field::Empty,
+                    gen_ai.system = "cohere",
+                    gen_ai.operation.name = operation,
+                    gen_ai.request.model = model,
+                    gen_ai.response.id = tracing::field::Empty,
+                    gen_ai.response.finish_reasons = tracing::field::Empty,
+                    gen_ai.usage.input_tokens = tracing::field::Empty,
+                    gen_ai.usage.output_tokens = tracing::field::Empty,
+                    cohere.endpoint = %request.endpoint,
+                    cohere.request_id = tracing::field::Empty,
+                    http.response.status_code = tracing::field::Empty,
+                    error.type = tracing::field::Empty,
+                    latency_ms = tracing::field::Empty,
+                )
+            },
+            #[cfg(feature = "metrics")]
+            labels: [
+                ("endpoint", request.endpoint.to_string()),
+                ("model", model.unwrap_or("default").to_string()),
+            ],
+            #[cfg(feature = "metrics")]
+            first_token_received: false,
+        }
+    }
+
+    /// Runs the future inside of the request's span.
+    pub fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        #[cfg(feature = "tracing")]
+        return tracing::Instrument::instrument(future, self.span.clone());
+        #[cfg(not(feature = "tracing"))]
+        future
+    }
+
+    /// Records the status and headers of the response.
+    pub fn response(&self, status: StatusCode, headers: &HeaderMap) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span
+                .record("http.response.status_code", status.as_u16());
+            if let Some(request_id) = headers
+                .get("x-request-id")
+                .and_then(|value| value.to_str().ok())
+            {
+                self.span.record("cohere.request_id", request_id);
+            }
+        }
+    }
+
+    /// Records a message of a streamed response, and the usage of the response when the stream ends.
+    pub fn stream_message(&mut self, line: &[u8]) {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        match StreamEvent::parse(line) {
+            #[cfg(feature = "metrics")]
+            Some(StreamEvent::TextGeneration) if !self.first_token_received => {
+                self.first_token_received = true;
+                metrics::histogram!("cohere_time_to_first_token_seconds", &self.labels)
+                    .record(self.start.elapsed().as_secs_f64());
+            }
+            Some(StreamEvent::End(end)) => {
+                #[cfg(feature = "tracing")]
+                if let Some(finish_reason) = &end.finish_reason {
+                    self.span
+                        .record("gen_ai.response.finish_reasons", finish_reason.as_str());
+                }
+                if let Some(usage) = &end.response {
+                    self.usage(usage);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn stream_started(&self) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, "cohere stream started");
+    }
+
+    pub fn stream_finished(self) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, "cohere stream finished");
+        self.finish(None);
+    }
+
+    /// Records the outcome of the call and closes its span.
+    pub fn finish(self, error: Option<&CohereApiError>) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span
+                .record("latency_ms", self.start.elapsed().as_millis() as u64);
+            if let Some(error) = error {
+                self.span.record("otel.status_code", "ERROR");
+                self.span
+                    .record("error.type", usage::error_type(error).as_str());
+                tracing::warn!(parent: &self.span, error = %error, "cohere request failed");
+            }
+        }
+        #[cfg(feature = "metrics")]
+        {
+            metrics::counter!("cohere_requests_total", &self.labels).increment(1);
+            metrics::histogram!("cohere_request_duration_seconds", &self.labels)
+                .record(self.start.elapsed().as_secs_f64());
+            if let Some(error) = error {
+                let [endpoint, model] = self.labels;
+                metrics::counter!(
+                    "cohere_errors_total",
+                    &[endpoint, model, ("status", usage::error_type(error))]
+                )
+                .increment(1);
+            }
+        }
+    }
+
+    /// Records the ID and billed units of a response.
+    pub fn usage(&self, response: &impl ResponseUsage) {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let billed_units = response
+            .meta()
+            .and_then(|meta| meta.billed_units)
+            .unwrap_or_default();
+        #[cfg(feature = "tracing")]
+        {
+            if let Some(id) = response.id() {
+                self.span.record("gen_ai.response.id", id);
+            }
+            if let Some(input_tokens) = billed_units.input_tokens {
+                self.span
+                    .record("gen_ai.usage.input_tokens", input_tokens as u64);
+            }
+            if let Some(output_tokens) = billed_units.output_tokens {
+                self.span
+                    .record("gen_ai.usage.output_tokens", output_tokens as u64);
+            }
+        }
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(input_tokens) = billed_units.input_tokens {
+                metrics::counter!("cohere_input_tokens_total", &self.labels)
+                    .increment(input_tokens as u64);
+            }
+            if let Some(output_tokens) = billed_units.output_tokens {
+                metrics::counter!("cohere_output_tokens_total", &self.labels)
+                    .increment(output_tokens as u64);
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+mod usage {
+    use serde::Deserialize;
+
+    use super::{ResponseMeta, ResponseUsage};
+    use crate::CohereApiError;
+
+    /// The operation name of an endpoint, following the OpenTelemetry GenAI semantic conventions where one exists.
+    #[cfg(feature = "tracing")]
+    pub fn operation_name(endpoint: crate::Endpoint) -> &'static str {
+        use crate::Endpoint;
+
+        match endpoint {
+            Endpoint::Chat => "chat",
+            Endpoint::Generate => "text_completion",
+            Endpoint::Embed => "embeddings",
+            Endpoint::CheckApiKey => "check_api_key",
+            Endpoint::Classify => "classify",
+            Endpoint::Tokenize => "tokenize",
+            Endpoint::Detokenize => "detokenize",
+            Endpoint::Rerank => "rerank",
+        }
+    }
+
+    pub fn error_type(error: &CohereApiError) -> String {
+        match error {
+            CohereApiError::ApiError(status, _) => status.as_u16().to_string(),
+            CohereApiError::RequestError(_) => "request_error".to_string(),
+            CohereApiError::InvalidApiKey => "invalid_api_key".to_string(),
+            CohereApiError::QueueFull => "queue_full".to_string(),
+            CohereApiError::JsonError(_) => "json_error".to_string(),
+            CohereApiError::TransportError(_) => "transport_error".to_string(),
+            CohereApiError::Unknown => "unknown".to_string(),
+        }
+    }
+
+    /// The messages of a streamed response that are instrumented.
+    pub enum StreamEvent {
+        TextGeneration,
+        End(StreamEnd),
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    pub struct StreamEnd {
+        #[serde(default)]
+        pub finish_reason: Option<String>,
+        #[serde(default)]
+        pub response: Option<StreamEndResponse>,
+    }
+
+    /// The response that ends a stream, of which only the parts that are instrumented are parsed.
+    #[derive(Deserialize, Debug)]
+    pub struct StreamEndResponse {
+        #[serde(default)]
+        id: Option<String>,
+        #[serde(default)]
+        response_id: Option<String>,
+        #[serde(default)]
+        meta: Option<ResponseMeta>,
+    }
+
+    impl ResponseUsage for StreamEndResponse {
+        fn id(&self) -> Option<&str> {
+            self.response_id.as_deref().or(self.id.as_deref())
+        }
+
+        fn meta(&self) -> Option<&ResponseMeta> {
+            self.meta.as_ref()
+        }
+    }
+
+    impl StreamEvent {
+        pub fn parse(line: &[u8]) -> Option<Self> {
+            #[derive(Deserialize)]
+            struct Event<'a> {
+                #[serde(borrow)]
+                event_type: Option<&'a str>,
+            }
+
+            // Avoid parsing the whole message unless it is the one that ends the stream
+            match serde_json::from_slice::<Event>(line).ok()?.event_type? {
+                "text-generation" => Some(StreamEvent::TextGeneration),
+                "stream-end" => serde_json::from_slice(line).ok().map(StreamEvent::End),
+                _ => None,
+            }
+        }
+    }
+}
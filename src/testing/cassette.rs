@@ -0,0 +1,269 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures_core::Stream;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    transport::{ReqwestTransport, ResponseBody, Transport, TransportRequest, TransportResponse},
+    CohereApiError, Endpoint,
+};
+
+const REDACTED: &str = "REDACTED";
+
+/// Request and response pairs recorded by a [`RecordingTransport`], which can be saved to a file
+/// and replayed with a [`ReplayTransport`].
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+/// A request and the response the API returned for it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordedRequest {
+    pub endpoint: Endpoint,
+    pub url: String,
+    /// The request's headers, with the API key redacted.
+    pub headers: BTreeMap<String, Vec<String>>,
+    pub body: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordedResponse {
+    pub status: u16,
+    /// The response's headers, with every value of a repeated header in the order it was received.
+    pub headers: BTreeMap<String, Vec<String>>,
+    /// The body as it was received, with each chunk of a streamed response recorded separately.
+    pub chunks: Vec<String>,
+}
+
+impl Cassette {
+    /// Loads a cassette saved with [`Cassette::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Saves the cassette as JSON, creating any missing parent directories.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// A transport that sends requests through another transport and records them in a [`Cassette`].
+///
+/// Streamed responses are recorded as they are read, so a stream has to be read to the end
+/// before the cassette is saved for its whole body to be recorded.
+pub struct RecordingTransport<T = ReqwestTransport> {
+    inner: T,
+    interactions: Arc<Mutex<Vec<Interaction>>>,
+}
+
+impl Default for RecordingTransport {
+    fn default() -> Self {
+        RecordingTransport::new(ReqwestTransport::default())
+    }
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    pub fn new(inner: T) -> Self {
+        RecordingTransport {
+            inner,
+            interactions: Arc::default(),
+        }
+    }
+
+    /// The interactions recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        Cassette {
+            interactions: self
+                .interactions
+                .lock()
+                .expect("cassette lock poisoned")
+                .clone(),
+        }
+    }
+
+    /// Saves the interactions recorded so far to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.cassette().save(path)
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl<T: Transport> Transport for RecordingTransport<T> {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, CohereApiError> {
+        let recorded_request = RecordedRequest {
+            endpoint: request.endpoint,
+            url: request.url.to_string(),
+            headers: header_map(&request.headers),
+            body: serde_json::from_slice(&request.body)?,
+        };
+
+        let response = self.inner.send(request).await?;
+
+        let index = {
+            let mut interactions = self.interactions.lock().expect("cassette lock poisoned");
+            interactions.push(Interaction {
+                request: recorded_request,
+                response: RecordedResponse {
+                    status: response.status.as_u16(),
+                    headers: header_map(&response.headers),
+                    chunks: Vec::new(),
+                },
+            });
+            interactions.len() - 1
+        };
+
+        Ok(TransportResponse {
+            body: ResponseBody::new(RecordingBody {
+                body: response.body,
+                interactions: self.interactions.clone(),
+                index,
+                partial: BytesMut::new(),
+            }),
+            ..response
+        })
+    }
+}
+
+/// Records the chunks of a response body as they are read.
+struct RecordingBody {
+    body: ResponseBody,
+    interactions: Arc<Mutex<Vec<Interaction>>>,
+    index: usize,
+    // The start of a character split across chunks
+    partial: BytesMut,
+}
+
+impl Stream for RecordingBody {
+    type Item = Result<Bytes, CohereApiError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let chunk = Pin::new(&mut self.body).poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &chunk {
+            self.partial.extend_from_slice(chunk);
+            let valid = match std::str::from_utf8(&self.partial) {
+                Ok(text) => text.len(),
+                Err(error) => error.valid_up_to(),
+            };
+            let text = self.partial.split_to(valid);
+            if !text.is_empty() {
+                let text = String::from_utf8(text.to_vec()).expect("chunk should be valid UTF-8");
+                let mut interactions = self.interactions.lock().expect("cassette lock poisoned");
+                interactions[self.index].response.chunks.push(text);
+            }
+        }
+        chunk
+    }
+}
+
+/// A transport that answers requests with the responses recorded in a [`Cassette`].
+///
+/// A request is answered by the first interaction that hasn't been replayed yet with the same
+/// endpoint and body. Requests without a matching interaction fail with `CohereApiError::TransportError`.
+pub struct ReplayTransport {
+    interactions: Mutex<Vec<Option<Interaction>>>,
+}
+
+impl ReplayTransport {
+    pub fn new(cassette: Cassette) -> Self {
+        ReplayTransport {
+            interactions: Mutex::new(cassette.interactions.into_iter().map(Some).collect()),
+        }
+    }
+
+    /// Loads a cassette saved with [`Cassette::save`] or [`RecordingTransport::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(ReplayTransport::new(Cassette::load(path)?))
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Transport for ReplayTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, CohereApiError> {
+        let body: serde_json::Value = serde_json::from_slice(&request.body)?;
+
+        let interaction = self
+            .interactions
+            .lock()
+            .expect("cassette lock poisoned")
+            .iter_mut()
+            .find(|interaction| {
+                interaction.as_ref().is_some_and(|interaction| {
+                    interaction.request.endpoint == request.endpoint
+                        && interaction.request.body == body
+                })
+            })
+            .and_then(Option::take)
+            .ok_or_else(|| {
+                CohereApiError::TransportError(
+                    format!(
+                        "no recorded interaction matches the request to the `{}` endpoint",
+                        request.endpoint
+                    )
+                    .into(),
+                )
+            })?;
+
+        let response = interaction.response;
+        let mut headers = HeaderMap::new();
+        for (name, values) in &response.headers {
+            let Ok(name) = HeaderName::try_from(name.as_str()) else {
+                continue;
+            };
+            for value in values {
+                if let Ok(value) = HeaderValue::try_from(value.as_str()) {
+                    headers.append(name.clone(), value);
+                }
+            }
+        }
+        Ok(TransportResponse {
+            status: StatusCode::from_u16(response.status)
+                .map_err(|error| CohereApiError::TransportError(error.into()))?,
+            headers,
+            body: response
+                .chunks
+                .into_iter()
+                .map(Bytes::from)
+                .collect::<Vec<_>>()
+                .into(),
+        })
+    }
+}
+
+/// Converts headers to a map that can be saved, redacting the API key.
+fn header_map(headers: &HeaderMap) -> BTreeMap<String, Vec<String>> {
+    let mut map = BTreeMap::<_, Vec<_>>::new();
+    for (name, value) in headers {
+        let value = match name == AUTHORIZATION || value.is_sensitive() {
+            true => REDACTED.to_string(),
+            false => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        };
+        map.entry(name.to_string()).or_default().push(value);
+    }
+    map
+}
@@ -0,0 +1,129 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    StatusCode,
+};
+use serde::Serialize;
+
+use crate::{
+    transport::{Transport, TransportRequest, TransportResponse},
+    CohereApiError, Endpoint,
+};
+
+/// A response returned by an [`InMemoryTransport`].
+#[derive(Clone, Debug)]
+pub struct InMemoryResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    chunks: Vec<Bytes>,
+}
+
+impl InMemoryResponse {
+    /// A successful response with the value serialized as its JSON body.
+    pub fn json<T: Serialize>(body: &T) -> Self {
+        let body = serde_json::to_vec(body).expect("response body should serialize to JSON");
+        InMemoryResponse::stream([body])
+    }
+
+    /// A successful streamed response made up of the given chunks.
+    /// Each message of a stream is a line of JSON, but chunks don't need to line up with messages.
+    pub fn stream<C: Into<Bytes>>(chunks: impl IntoIterator<Item = C>) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        InMemoryResponse {
+            status: StatusCode::OK,
+            headers,
+            chunks: chunks.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// An unsuccessful response with the error message the API would return.
+    pub fn error(status: StatusCode, message: &str) -> Self {
+        InMemoryResponse::json(&serde_json::json!({ "message": message })).status(status)
+    }
+
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+}
+
+/// A transport that answers requests with queued responses instead of calling the API,
+/// and records every request it receives.
+///
+/// Responses are queued per endpoint and returned in the order they were queued.
+/// A request to an endpoint without a queued response fails with `CohereApiError::TransportError`.
+#[derive(Default)]
+pub struct InMemoryTransport {
+    responses: Mutex<HashMap<Endpoint, VecDeque<InMemoryResponse>>>,
+    requests: Mutex<Vec<TransportRequest>>,
+}
+
+impl InMemoryTransport {
+    pub fn new() -> Self {
+        InMemoryTransport::default()
+    }
+
+    /// Queues a response for the next request to the endpoint.
+    pub fn push(&self, endpoint: Endpoint, response: InMemoryResponse) -> &Self {
+        self.responses
+            .lock()
+            .expect("in-memory transport lock poisoned")
+            .entry(endpoint)
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// The requests received so far, in the order they were sent.
+    pub fn requests(&self) -> Vec<TransportRequest> {
+        self.requests
+            .lock()
+            .expect("in-memory transport lock poisoned")
+            .clone()
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Transport for InMemoryTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, CohereApiError> {
+        let endpoint = request.endpoint;
+        self.requests
+            .lock()
+            .expect("in-memory transport lock poisoned")
+            .push(request);
+
+        let response = self
+            .responses
+            .lock()
+            .expect("in-memory transport lock poisoned")
+            .get_mut(&endpoint)
+            .and_then(VecDeque::pop_front)
+            .ok_or_else(|| {
+                CohereApiError::TransportError(
+                    format!("no response queued for the `{endpoint}` endpoint").into(),
+                )
+            })?;
+
+        Ok(TransportResponse {
+            status: response.status,
+            headers: response.headers,
+            body: response.chunks.into(),
+        })
+    }
+}
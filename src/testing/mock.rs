@@ -0,0 +1,262 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    api::{
+        chat::{ChatRequest, ChatStreamResponse},
+        classify::{Classification, ClassifyRequest},
+        detokenize::DetokenizeRequest,
+        embed::EmbedRequest,
+        generate::{GenerateRequest, GenerateStreamResponse, Generation},
+        rerank::{ReRankRequest, ReRankResult},
+        tokenize::{TokenizeRequest, TokenizeResponse},
+    },
+    CohereApi, CohereApiError, CohereStream, Endpoint,
+};
+
+/// A call made to a [`MockCohere`], with an owned copy of its request.
+#[derive(Clone, Debug)]
+pub enum MockCall {
+    CheckApiKey,
+    Generate(GenerateRequest<'static>),
+    GenerateStream(GenerateRequest<'static>),
+    Chat(ChatRequest<'static>),
+    Embed(EmbedRequest<'static>),
+    Classify(ClassifyRequest<'static>),
+    Tokenize(TokenizeRequest<'static>),
+    Detokenize(DetokenizeRequest<'static>),
+    Rerank(ReRankRequest<'static>),
+}
+
+enum MockResponse {
+    CheckApiKey,
+    Generate(Vec<Generation>),
+    GenerateStream(Vec<GenerateStreamResponse>),
+    Chat(Vec<ChatStreamResponse>),
+    Embed(Vec<Vec<f64>>),
+    Classify(Vec<Classification>),
+    Tokenize(TokenizeResponse),
+    Detokenize(String),
+    Rerank(Vec<ReRankResult>),
+    Error(CohereApiError),
+}
+
+/// A fake implementation of [`CohereApi`] that returns scripted responses and records every call.
+///
+/// Responses are queued per endpoint and returned in the order they were queued.
+/// Calling an endpoint without a queued response panics, as does queuing a streamed
+/// response for a call that isn't streamed or the other way around.
+///
+/// ```
+/// # use cohere_rust::{testing::MockCohere, CohereApi, api::embed::EmbedRequest};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mock = MockCohere::new();
+/// mock.push_embed(vec![vec![0.5, -1.25]]);
+///
+/// let embeddings = mock.embed(&EmbedRequest::default()).await.unwrap();
+/// assert_eq!(vec![vec![0.5, -1.25]], embeddings);
+/// assert_eq!(1, mock.calls().len());
+/// # }
+/// ```
+#[derive(Default)]
+pub struct MockCohere {
+    responses: Mutex<HashMap<Endpoint, VecDeque<MockResponse>>>,
+    calls: Mutex<Vec<MockCall>>,
+}
+
+impl MockCohere {
+    pub fn new() -> Self {
+        MockCohere::default()
+    }
+
+    /// Queues a successful response for the next call to `check_api_key`.
+    pub fn push_check_api_key(&self) -> &Self {
+        self.push(Endpoint::CheckApiKey, MockResponse::CheckApiKey)
+    }
+
+    /// Queues the generations returned by the next call to `generate`.
+    pub fn push_generate(&self, generations: Vec<Generation>) -> &Self {
+        self.push(Endpoint::Generate, MockResponse::Generate(generations))
+    }
+
+    /// Queues the messages streamed by the next call to `generate_stream`.
+    pub fn push_generate_stream(&self, messages: Vec<GenerateStreamResponse>) -> &Self {
+        self.push(Endpoint::Generate, MockResponse::GenerateStream(messages))
+    }
+
+    /// Queues the messages streamed by the next call to `chat`.
+    pub fn push_chat(&self, messages: Vec<ChatStreamResponse>) -> &Self {
+        self.push(Endpoint::Chat, MockResponse::Chat(messages))
+    }
+
+    /// Queues the embeddings returned by the next call to `embed`.
+    pub fn push_embed(&self, embeddings: Vec<Vec<f64>>) -> &Self {
+        self.push(Endpoint::Embed, MockResponse::Embed(embeddings))
+    }
+
+    /// Queues the classifications returned by the next call to `classify`.
+    pub fn push_classify(&self, classifications: Vec<Classification>) -> &Self {
+        self.push(Endpoint::Classify, MockResponse::Classify(classifications))
+    }
+
+    /// Queues the response returned by the next call to `tokenize`.
+    pub fn push_tokenize(&self, response: TokenizeResponse) -> &Self {
+        self.push(Endpoint::Tokenize, MockResponse::Tokenize(response))
+    }
+
+    /// Queues the text returned by the next call to `detokenize`.
+    pub fn push_detokenize<S: Into<String>>(&self, text: S) -> &Self {
+        self.push(Endpoint::Detokenize, MockResponse::Detokenize(text.into()))
+    }
+
+    /// Queues the results returned by the next call to `rerank`.
+    pub fn push_rerank(&self, results: Vec<ReRankResult>) -> &Self {
+        self.push(Endpoint::Rerank, MockResponse::Rerank(results))
+    }
+
+    /// Queues an error for the next call to the endpoint.
+    pub fn push_error(&self, endpoint: Endpoint, error: CohereApiError) -> &Self {
+        self.push(endpoint, MockResponse::Error(error))
+    }
+
+    /// The calls made so far, in the order they were made.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().expect("mock lock poisoned").clone()
+    }
+
+    fn push(&self, endpoint: Endpoint, response: MockResponse) -> &Self {
+        self.responses
+            .lock()
+            .expect("mock lock poisoned")
+            .entry(endpoint)
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// Records the call and takes the next response queued for its endpoint.
+    fn call(&self, endpoint: Endpoint, call: MockCall) -> Result<MockResponse, CohereApiError> {
+        self.calls.lock().expect("mock lock poisoned").push(call);
+
+        let response = self
+            .responses
+            .lock()
+            .expect("mock lock poisoned")
+            .get_mut(&endpoint)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or_else(|| panic!("MockCohere has no response queued for `{endpoint}`"));
+
+        match response {
+            MockResponse::Error(error) => Err(error),
+            response => Ok(response),
+        }
+    }
+}
+
+fn unexpected(endpoint: Endpoint, method: &str) -> ! {
+    panic!("MockCohere has a response queued for `{endpoint}` that can't be returned by `{method}`")
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl CohereApi for MockCohere {
+    async fn check_api_key(&self) -> Result<(), CohereApiError> {
+        match self.call(Endpoint::CheckApiKey, MockCall::CheckApiKey)? {
+            MockResponse::CheckApiKey => Ok(()),
+            _ => unexpected(Endpoint::CheckApiKey, "check_api_key"),
+        }
+    }
+
+    async fn generate<'input>(
+        &self,
+        request: &GenerateRequest<'input>,
+    ) -> Result<Vec<Generation>, CohereApiError> {
+        let call = MockCall::Generate(request.clone().into_owned());
+        match self.call(Endpoint::Generate, call)? {
+            MockResponse::Generate(generations) => Ok(generations),
+            _ => unexpected(Endpoint::Generate, "generate"),
+        }
+    }
+
+    async fn generate_stream<'input>(
+        &self,
+        request: &GenerateRequest<'input>,
+    ) -> Result<CohereStream<GenerateStreamResponse>, CohereApiError> {
+        let call = MockCall::GenerateStream(request.clone().into_owned());
+        match self.call(Endpoint::Generate, call)? {
+            MockResponse::GenerateStream(messages) => Ok(CohereStream::from_messages(messages)),
+            _ => unexpected(Endpoint::Generate, "generate_stream"),
+        }
+    }
+
+    async fn chat<'input>(
+        &self,
+        request: &ChatRequest<'input>,
+    ) -> Result<CohereStream<ChatStreamResponse>, CohereApiError> {
+        let call = MockCall::Chat(request.clone().into_owned());
+        match self.call(Endpoint::Chat, call)? {
+            MockResponse::Chat(messages) => Ok(CohereStream::from_messages(messages)),
+            _ => unexpected(Endpoint::Chat, "chat"),
+        }
+    }
+
+    async fn embed<'input>(
+        &self,
+        request: &EmbedRequest<'input>,
+    ) -> Result<Vec<Vec<f64>>, CohereApiError> {
+        let call = MockCall::Embed(request.clone().into_owned());
+        match self.call(Endpoint::Embed, call)? {
+            MockResponse::Embed(embeddings) => Ok(embeddings),
+            _ => unexpected(Endpoint::Embed, "embed"),
+        }
+    }
+
+    async fn classify<'input>(
+        &self,
+        request: &ClassifyRequest<'input>,
+    ) -> Result<Vec<Classification>, CohereApiError> {
+        let call = MockCall::Classify(request.clone().into_owned());
+        match self.call(Endpoint::Classify, call)? {
+            MockResponse::Classify(classifications) => Ok(classifications),
+            _ => unexpected(Endpoint::Classify, "classify"),
+        }
+    }
+
+    async fn tokenize<'input>(
+        &self,
+        request: &TokenizeRequest<'input>,
+    ) -> Result<TokenizeResponse, CohereApiError> {
+        let call = MockCall::Tokenize(request.clone().into_owned());
+        match self.call(Endpoint::Tokenize, call)? {
+            MockResponse::Tokenize(response) => Ok(response),
+            _ => unexpected(Endpoint::Tokenize, "tokenize"),
+        }
+    }
+
+    async fn detokenize<'input>(
+        &self,
+        request: &DetokenizeRequest<'input>,
+    ) -> Result<String, CohereApiError> {
+        let call = MockCall::Detokenize(request.clone().into_owned());
+        match self.call(Endpoint::Detokenize, call)? {
+            MockResponse::Detokenize(text) => Ok(text),
+            _ => unexpected(Endpoint::Detokenize, "detokenize"),
+        }
+    }
+
+    async fn rerank<'input>(
+        &self,
+        request: &ReRankRequest<'input>,
+    ) -> Result<Vec<ReRankResult>, CohereApiError> {
+        let call = MockCall::Rerank(request.clone().into_owned());
+        match self.call(Endpoint::Rerank, call)? {
+            MockResponse::Rerank(results) => Ok(results),
+            _ => unexpected(Endpoint::Rerank, "rerank"),
+        }
+    }
+}
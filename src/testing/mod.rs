@@ -0,0 +1,16 @@
+//! Utilities for testing code that uses the client without calling the Cohere API.
+//! Enabled by the `test-util` feature.
+
+mod cassette;
+mod memory;
+mod mock;
+#[cfg(not(target_arch = "wasm32"))]
+mod server;
+
+pub use cassette::{
+    Cassette, Interaction, RecordedRequest, RecordedResponse, RecordingTransport, ReplayTransport,
+};
+pub use memory::{InMemoryResponse, InMemoryTransport};
+pub use mock::{MockCall, MockCohere};
+#[cfg(not(target_arch = "wasm32"))]
+pub use server::{FakeFailure, FakeRequest, FakeServer, FAKE_EMBEDDING_DIMENSIONS};
@@ -0,0 +1,528 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+use crate::Endpoint;
+
+/// The number of dimensions of the embeddings returned by the [`FakeServer`].
+pub const FAKE_EMBEDDING_DIMENSIONS: usize = 8;
+
+/// A failure injected into the next response of a [`FakeServer`] endpoint.
+#[derive(Clone, Debug)]
+pub enum FakeFailure {
+    /// Responds with `429 Too Many Requests`, and a `Retry-After` header if a delay is given.
+    RateLimited(Option<Duration>),
+    /// Responds with `500 Internal Server Error`.
+    ServerError,
+    /// Responds with the given status and error message.
+    Status(StatusCode, String),
+    /// Starts a successful stream and then sends a message that isn't valid JSON before closing it.
+    MalformedStream,
+    /// Starts a successful response and then closes the connection before the body is complete.
+    Disconnect,
+}
+
+/// A request received by a [`FakeServer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FakeRequest {
+    pub endpoint: Endpoint,
+    pub headers: HashMap<String, String>,
+    pub body: Value,
+}
+
+/// A local HTTP server implementing the Cohere API endpoints supported by the client with
+/// deterministic fake outputs, for testing without calling the API.
+///
+/// The server runs on the current tokio runtime until it is dropped. Point a client at it with
+/// `Cohere::builder().api_url(server.url())`.
+///
+/// Responses are derived from the requests:
+/// - `chat` and `generate` repeat the message or prompt back, streamed one word at a time.
+/// - `embed` returns [`FAKE_EMBEDDING_DIMENSIONS`] values derived from a hash of each text.
+/// - `rerank` scores documents by the share of the query's words their `rank_fields` contain.
+/// - `classify` predicts the label of the example sharing the most words with each input.
+/// - `tokenize` splits text on whitespace, and `detokenize` joins the words of known tokens.
+pub struct FakeServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<ServerState>>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct ServerState {
+    latency: Duration,
+    stream_delay: Duration,
+    failures: HashMap<Endpoint, VecDeque<FakeFailure>>,
+    requests: Vec<FakeRequest>,
+    vocabulary: HashMap<u64, String>,
+}
+
+impl FakeServer {
+    /// Starts the server on a random local port.
+    pub async fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(ServerState::default()));
+
+        let task = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        // A client that hangs up early isn't an error for the server
+                        let _ = handle_connection(stream, state).await;
+                    });
+                }
+            }
+        });
+
+        Ok(FakeServer { addr, state, task })
+    }
+
+    /// The base URL of the server's API.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Delays every response by the given duration.
+    pub fn set_latency(&self, latency: Duration) {
+        self.state().latency = latency;
+    }
+
+    /// Delays every message of a streamed response by the given duration.
+    pub fn set_stream_delay(&self, delay: Duration) {
+        self.state().stream_delay = delay;
+    }
+
+    /// Fails the next request to the endpoint. Failures are queued and used in order.
+    pub fn fail_next(&self, endpoint: Endpoint, failure: FakeFailure) {
+        self.state()
+            .failures
+            .entry(endpoint)
+            .or_default()
+            .push_back(failure);
+    }
+
+    /// The requests received so far, in the order they were received.
+    pub fn requests(&self) -> Vec<FakeRequest> {
+        self.state().requests.clone()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ServerState> {
+        self.state.lock().expect("fake server lock poisoned")
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The body of a response, which is either sent at once or streamed one line at a time.
+enum Body {
+    Json(Value),
+    Stream(Vec<String>),
+}
+
+async fn handle_connection(stream: TcpStream, state: Arc<Mutex<ServerState>>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    let mut stream = reader.into_inner();
+
+    let endpoint = path.trim_start_matches("/v1").trim_start_matches('/');
+    let Ok(endpoint) = serde_json::from_value::<Endpoint>(json!(endpoint)) else {
+        return write_error(&mut stream, StatusCode::NOT_FOUND, "endpoint not found").await;
+    };
+    let Ok(body) = serde_json::from_slice::<Value>(&body) else {
+        return write_error(&mut stream, StatusCode::BAD_REQUEST, "invalid JSON body").await;
+    };
+
+    let (latency, stream_delay, failure, response) = {
+        let mut state = state.lock().expect("fake server lock poisoned");
+        state.requests.push(FakeRequest {
+            endpoint,
+            headers,
+            body: body.clone(),
+        });
+        let failure = state
+            .failures
+            .get_mut(&endpoint)
+            .and_then(VecDeque::pop_front);
+        let response = respond(endpoint, &body, &mut state.vocabulary);
+        (state.latency, state.stream_delay, failure, response)
+    };
+
+    tokio::time::sleep(latency).await;
+
+    match (failure, response) {
+        (Some(FakeFailure::RateLimited(retry_after)), _) => {
+            let header = retry_after
+                .map(|delay| format!("Retry-After: {}\r\n", delay.as_secs_f64()))
+                .unwrap_or_default();
+            let body = json!({ "message": "You are being rate limited" }).to_string();
+            write_response(&mut stream, StatusCode::TOO_MANY_REQUESTS, &header, &body).await
+        }
+        (Some(FakeFailure::ServerError), _) => {
+            let status = StatusCode::INTERNAL_SERVER_ERROR;
+            write_error(&mut stream, status, "internal server error").await
+        }
+        (Some(FakeFailure::Status(status, message)), _) => {
+            write_error(&mut stream, status, &message).await
+        }
+        (Some(FakeFailure::MalformedStream), Body::Stream(lines)) => {
+            let lines = lines
+                .into_iter()
+                .take(1)
+                .chain(["{\"event_type\":".to_string()]);
+            write_stream(&mut stream, lines, stream_delay, true).await
+        }
+        (Some(FakeFailure::MalformedStream), Body::Json(_)) => {
+            write_response(&mut stream, StatusCode::OK, "", "{\"malformed\":").await
+        }
+        (Some(FakeFailure::Disconnect), Body::Stream(lines)) => {
+            let lines = lines.into_iter().take(1);
+            write_stream(&mut stream, lines, stream_delay, false).await
+        }
+        (Some(FakeFailure::Disconnect), Body::Json(body)) => {
+            let body = body.to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                &body[..body.len() / 2]
+            );
+            stream.write_all(response.as_bytes()).await?;
+            stream.shutdown().await
+        }
+        (None, Body::Json(body)) => {
+            write_response(&mut stream, StatusCode::OK, "", &body.to_string()).await
+        }
+        (None, Body::Stream(lines)) => write_stream(&mut stream, lines, stream_delay, true).await,
+    }
+}
+
+async fn write_error(stream: &mut TcpStream, status: StatusCode, message: &str) -> io::Result<()> {
+    let body = json!({ "message": message }).to_string();
+    write_response(stream, status, "", &body).await
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    status: StatusCode,
+    headers: &str,
+    body: &str,
+) -> io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n{headers}\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+async fn write_stream(
+    stream: &mut TcpStream,
+    lines: impl IntoIterator<Item = String>,
+    delay: Duration,
+    complete: bool,
+) -> io::Result<()> {
+    stream
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: application/stream+json\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n")
+        .await?;
+    for line in lines {
+        tokio::time::sleep(delay).await;
+        let chunk = format!("{:x}\r\n{line}\n\r\n", line.len() + 1);
+        stream.write_all(chunk.as_bytes()).await?;
+        stream.flush().await?;
+    }
+    // The last chunk is left out to end the response before it is complete
+    if complete {
+        stream.write_all(b"0\r\n\r\n").await?;
+    }
+    stream.shutdown().await
+}
+
+fn respond(endpoint: Endpoint, body: &Value, vocabulary: &mut HashMap<u64, String>) -> Body {
+    let id = format!("{:016x}", hash(&body.to_string()));
+    match endpoint {
+        Endpoint::CheckApiKey => Body::Json(json!({ "valid": true })),
+        Endpoint::Chat => chat(id, body),
+        Endpoint::Generate => generate(id, body),
+        Endpoint::Embed => {
+            let texts = strings(&body["texts"]);
+            let embeddings = texts.iter().map(|text| embedding(text)).collect::<Vec<_>>();
+            Body::Json(json!({ "id": id, "texts": texts, "embeddings": embeddings }))
+        }
+        Endpoint::Rerank => Body::Json(json!({ "id": id, "results": rerank(body) })),
+        Endpoint::Classify => {
+            let classifications = classify(&id, body);
+            Body::Json(json!({ "id": id, "classifications": classifications }))
+        }
+        Endpoint::Tokenize => {
+            let token_strings = words(body["text"].as_str().unwrap_or_default());
+            let tokens = token_strings
+                .iter()
+                .map(|word| {
+                    let token = hash(word) % 100_000;
+                    vocabulary.insert(token, word.clone());
+                    token
+                })
+                .collect::<Vec<_>>();
+            Body::Json(json!({ "tokens": tokens, "token_strings": token_strings }))
+        }
+        Endpoint::Detokenize => {
+            let text = body["tokens"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|token| vocabulary.get(&token.as_u64()?).cloned())
+                .collect::<Vec<_>>()
+                .join(" ");
+            Body::Json(json!({ "text": text }))
+        }
+    }
+}
+
+fn chat(id: String, body: &Value) -> Body {
+    let message = body["message"].as_str().unwrap_or_default();
+    let text = format!("You said: {message}");
+    let response = json!({
+        "response_id": id,
+        "generation_id": id,
+        "text": text,
+        "meta": {
+            "billed_units": {
+                "input_tokens": words(message).len(),
+                "output_tokens": words(&text).len(),
+            },
+        },
+    });
+    if body["stream"].as_bool() != Some(true) {
+        return Body::Json(response);
+    }
+
+    let start = json!({
+        "is_finished": false,
+        "event_type": "stream-start",
+        "generation_id": id,
+    });
+    let mut lines = vec![start.to_string()];
+    lines.extend(text_generations(&text));
+    lines.push(stream_end(response));
+    Body::Stream(lines)
+}
+
+fn generate(id: String, body: &Value) -> Body {
+    let text = body["prompt"].as_str().unwrap_or_default();
+    let generations = (0..body["num_generations"].as_u64().unwrap_or(1))
+        .map(|index| {
+            json!({
+                "id": format!("{id}-{index}"),
+                "text": text,
+                "finish_reason": "COMPLETE",
+            })
+        })
+        .collect::<Vec<_>>();
+    let response = json!({ "id": id, "generations": generations });
+    if body["stream"].as_bool() != Some(true) {
+        return Body::Json(response);
+    }
+
+    let mut lines = text_generations(text);
+    lines.push(stream_end(response));
+    Body::Stream(lines)
+}
+
+/// Values in `[-1, 1]` derived from a hash of the text and each dimension.
+fn embedding(text: &str) -> Vec<f64> {
+    (0..FAKE_EMBEDDING_DIMENSIONS)
+        .map(|dimension| (hash(&format!("{dimension}:{text}")) % 2001) as f64 / 1000.0 - 1.0)
+        .collect()
+}
+
+fn rerank(body: &Value) -> Vec<Value> {
+    let query = words(body["query"].as_str().unwrap_or_default());
+    let mut rank_fields = strings(&body["rank_fields"]);
+    if rank_fields.is_empty() {
+        rank_fields.push("text".to_string());
+    }
+    // Text documents are treated as objects with a `text` field, which is how they are returned
+    let documents = body["documents"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|document| match document {
+            Value::String(text) => json!({ "text": text }),
+            document => document.clone(),
+        })
+        .collect::<Vec<_>>();
+    let mut results = documents
+        .iter()
+        .map(|document| {
+            rank_fields
+                .iter()
+                .filter_map(|field| document[field].as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .enumerate()
+        .map(|(index, text)| (index, overlap(&query, &words(&text))))
+        .map(|(index, matches)| (index, matches as f64 / query.len().max(1) as f64))
+        .collect::<Vec<_>>();
+    // The sort is stable so documents with the same score keep their order
+    results.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let top_n = body["top_n"].as_u64().map_or(results.len(), |n| n as usize);
+    let return_documents = body["return_documents"].as_bool().unwrap_or_default();
+    results
+        .into_iter()
+        .take(top_n)
+        .map(|(index, score)| {
+            let mut result = json!({ "index": index, "relevance_score": score });
+            if return_documents {
+                result["document"] = documents[index].clone();
+            }
+            result
+        })
+        .collect()
+}
+
+fn classify(id: &str, body: &Value) -> Vec<Value> {
+    let examples = body["examples"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|example| {
+            let text = words(example["text"].as_str().unwrap_or_default());
+            (text, example["label"].as_str().unwrap_or_default())
+        })
+        .collect::<Vec<_>>();
+    let mut labels: Vec<&str> = Vec::new();
+    for (_, label) in &examples {
+        if !labels.contains(label) {
+            labels.push(label);
+        }
+    }
+
+    strings(&body["inputs"])
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            // Each label scores one more than the most words an input shares with its examples
+            let input_words = words(input);
+            let scores = labels
+                .iter()
+                .map(|label| {
+                    let matches = examples
+                        .iter()
+                        .filter(|(_, example_label)| example_label == label)
+                        .map(|(text, _)| overlap(&input_words, text))
+                        .max()
+                        .unwrap_or(0);
+                    (*label, matches as f64 + 1.0)
+                })
+                .collect::<Vec<_>>();
+            let total = scores.iter().map(|(_, score)| score).sum::<f64>();
+
+            // The first label wins a tie
+            let (prediction, score) = scores
+                .iter()
+                .copied()
+                .reduce(|best, score| if score.1 > best.1 { score } else { best })
+                .unwrap_or_default();
+            let labels = scores
+                .iter()
+                .map(|(label, score)| (label.to_string(), json!({ "confidence": score / total })))
+                .collect::<serde_json::Map<_, _>>();
+            json!({
+                "id": format!("{id}-{index}"),
+                "input": input,
+                "prediction": prediction,
+                "confidence": if total > 0.0 { score / total } else { 0.0 },
+                "labels": labels,
+            })
+        })
+        .collect()
+}
+
+fn text_generations(text: &str) -> Vec<String> {
+    text.split_inclusive(' ')
+        .map(|word| {
+            json!({ "is_finished": false, "event_type": "text-generation", "text": word })
+                .to_string()
+        })
+        .collect()
+}
+
+fn stream_end(response: Value) -> String {
+    json!({
+        "is_finished": true,
+        "event_type": "stream-end",
+        "finish_reason": "COMPLETE",
+        "response": response,
+    })
+    .to_string()
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|value| value.as_str().map(str::to_string))
+        .collect()
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace().map(str::to_lowercase).collect()
+}
+
+/// The number of words in `words` that are also in `other`.
+fn overlap(words: &[String], other: &[String]) -> usize {
+    words.iter().filter(|word| other.contains(word)).count()
+}
+
+/// FNV-1a, which unlike the standard library's hasher is stable across runs and platforms.
+fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,262 @@
+//! Tokenization with the model's tokenizer on the local machine, without a round-trip to the API.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use thiserror::Error;
+use tokenizers::Tokenizer;
+
+use crate::{
+    api::{tokenize::TokenizeResponse, GenerateModel},
+    history::TokenCounter,
+    parse_error, Cohere, CohereApiError,
+};
+
+#[derive(Error, Debug)]
+pub enum TokenizerError {
+    #[error("Failed to read or cache the tokenizer")]
+    Io(#[from] io::Error),
+    #[error("Failed to download the tokenizer")]
+    Download(#[from] reqwest::Error),
+    #[error("Failed to look up the model: {0}")]
+    Model(CohereApiError),
+    #[error("Model `{0}` has no tokenizer")]
+    NoTokenizer(String),
+    #[error("Tokenizer error: {0}")]
+    Tokenizer(tokenizers::Error),
+    #[error("Token `{0}` is not in the tokenizer's vocabulary")]
+    InvalidToken(u64),
+}
+
+/// The part of a model's details from the models API that is needed to load its tokenizer.
+#[derive(Deserialize, Debug)]
+struct ModelDetails {
+    tokenizer_url: Option<String>,
+}
+
+/// A model's tokenizer, loaded from the JSON file that defines it.
+///
+/// The tokenizer of a model is published at the `tokenizer_url` returned for it by the models API.
+/// [`Cohere::local_tokenizer`] looks it up, downloads it once and caches it on disk, so that text can then be
+/// tokenized offline, which is much faster than calling the `tokenize` endpoint for each text.
+///
+/// ```no_run
+/// # use cohere_rust::{api::GenerateModel, Cohere};
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let co = Cohere::default();
+/// let tokenizer = co.local_tokenizer(&GenerateModel::CommandR).await?;
+///
+/// let response = tokenizer.tokenize("tokenize me!")?;
+/// assert_eq!(response.tokens.len(), tokenizer.count_tokens("tokenize me!")?);
+/// assert_eq!("tokenize me!", tokenizer.detokenize(&response.tokens)?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LocalTokenizer {
+    tokenizer: Tokenizer,
+}
+
+impl Cohere {
+    /// Loads the tokenizer of a model, whose `tokenizer_url` is looked up with the models API.
+    ///
+    /// The tokenizer is downloaded with the client's TLS settings and cached like [`LocalTokenizer::from_url`].
+    /// The lookup is sent directly rather than through the client's transport, rate limits and middleware.
+    pub async fn local_tokenizer(
+        &self,
+        model: &GenerateModel,
+    ) -> Result<LocalTokenizer, TokenizerError> {
+        self.local_tokenizer_cached_in(model, default_cache_dir())
+            .await
+    }
+
+    /// Loads the tokenizer of a model, caching it in the given directory.
+    pub async fn local_tokenizer_cached_in(
+        &self,
+        model: &GenerateModel,
+        cache_dir: impl AsRef<Path>,
+    ) -> Result<LocalTokenizer, TokenizerError> {
+        let name = match model {
+            GenerateModel::Custom(name) => name.clone(),
+            model => model.to_string(),
+        };
+        let response = self
+            .http_client
+            .get(format!("{}/models/{name}", self.api_url))
+            .headers(self.headers.clone())
+            .send()
+            .await
+            .map_err(|error| TokenizerError::Model(error.into()))?;
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|error| TokenizerError::Model(error.into()))?;
+        if status.is_client_error() || status.is_server_error() {
+            return Err(TokenizerError::Model(parse_error(status, &body)));
+        }
+
+        let details: ModelDetails =
+            serde_json::from_slice(&body).map_err(|error| TokenizerError::Model(error.into()))?;
+        let url = details
+            .tokenizer_url
+            .ok_or(TokenizerError::NoTokenizer(name))?;
+        LocalTokenizer::from_url_cached_in(&self.http_client, &url, cache_dir).await
+    }
+}
+
+impl LocalTokenizer {
+    /// Loads a tokenizer from its JSON definition.
+    pub fn from_bytes(json: impl AsRef<[u8]>) -> Result<Self, TokenizerError> {
+        let tokenizer = Tokenizer::from_bytes(json).map_err(TokenizerError::Tokenizer)?;
+        Ok(LocalTokenizer { tokenizer })
+    }
+
+    /// Loads a tokenizer from a JSON file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, TokenizerError> {
+        LocalTokenizer::from_bytes(fs::read(path)?)
+    }
+
+    /// Loads a tokenizer from a URL, such as the `tokenizer_url` of a model, downloading it with the HTTP client
+    /// only if it isn't cached yet.
+    ///
+    /// Tokenizers are cached in `$XDG_CACHE_HOME/cohere-rust/tokenizers`, or `~/.cache/cohere-rust/tokenizers`
+    /// if `XDG_CACHE_HOME` isn't set.
+    pub async fn from_url(client: &reqwest::Client, url: &str) -> Result<Self, TokenizerError> {
+        LocalTokenizer::from_url_cached_in(client, url, default_cache_dir()).await
+    }
+
+    /// Loads a tokenizer from a URL, caching it in the given directory.
+    pub async fn from_url_cached_in(
+        client: &reqwest::Client,
+        url: &str,
+        cache_dir: impl AsRef<Path>,
+    ) -> Result<Self, TokenizerError> {
+        let path = cache_dir.as_ref().join(cache_file_name(url));
+        match tokio::fs::read(&path).await {
+            Ok(json) => return LocalTokenizer::from_bytes(json),
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+            Err(_) => {}
+        }
+
+        let json = client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        // Only valid tokenizers are cached
+        let tokenizer = LocalTokenizer::from_bytes(&json)?;
+
+        // The file is renamed into place so that concurrent loads never read a partial file
+        tokio::fs::create_dir_all(cache_dir.as_ref()).await?;
+        let partial = path.with_extension(format!("{}.partial", process::id()));
+        tokio::fs::write(&partial, &json).await?;
+        tokio::fs::rename(&partial, &path).await?;
+
+        Ok(tokenizer)
+    }
+
+    /// Splits text into tokens, like the `tokenize` endpoint.
+    pub fn tokenize(&self, text: &str) -> Result<TokenizeResponse, TokenizerError> {
+        let encoding = self
+            .tokenizer
+            .encode(text, false)
+            .map_err(TokenizerError::Tokenizer)?;
+        let token_strings = encoding
+            .get_ids()
+            .iter()
+            .map(|&id| self.tokenizer.decode(&[id], false))
+            .collect::<Result<_, _>>()
+            .map_err(TokenizerError::Tokenizer)?;
+
+        Ok(TokenizeResponse {
+            tokens: encoding.get_ids().iter().map(|&id| id.into()).collect(),
+            token_strings,
+        })
+    }
+
+    /// Returns the text of the tokens, like the `detokenize` endpoint.
+    pub fn detokenize(&self, tokens: &[u64]) -> Result<String, TokenizerError> {
+        let ids = tokens
+            .iter()
+            .map(|&token| u32::try_from(token).map_err(|_| TokenizerError::InvalidToken(token)))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.tokenizer
+            .decode(&ids, false)
+            .map_err(TokenizerError::Tokenizer)
+    }
+
+    /// Counts the tokens of the text.
+    pub fn count_tokens(&self, text: &str) -> Result<usize, TokenizerError> {
+        let encoding = self
+            .tokenizer
+            .encode(text, false)
+            .map_err(TokenizerError::Tokenizer)?;
+        Ok(encoding.len())
+    }
+
+    /// Counts the tokens of each text, tokenizing the texts in parallel.
+    pub fn count_tokens_batch(&self, texts: &[&str]) -> Result<Vec<usize>, TokenizerError> {
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts.to_vec(), false)
+            .map_err(TokenizerError::Tokenizer)?;
+        Ok(encodings.iter().map(|encoding| encoding.len()).collect())
+    }
+}
+
+/// Counts tokens with the tokenizer regardless of the model, which should be the model the tokenizer belongs to.
+#[async_trait]
+impl TokenCounter for LocalTokenizer {
+    async fn count_tokens(
+        &self,
+        text: &str,
+        _model: Option<&GenerateModel>,
+    ) -> Result<usize, CohereApiError> {
+        // The tokenizer takes the place of the API, so its errors are reported like those of a transport
+        LocalTokenizer::count_tokens(self, text)
+            .map_err(|error| CohereApiError::TransportError(error.into()))
+    }
+}
+
+fn default_cache_dir() -> PathBuf {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(env::temp_dir)
+        .join("cohere-rust")
+        .join("tokenizers")
+}
+
+/// The name of the file a URL is cached in, which is unique to the URL and ends with its readable file name.
+fn cache_file_name(url: &str) -> String {
+    let file_name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .unwrap_or_default();
+    let file_name: String = file_name
+        .chars()
+        .take(64)
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("{:016x}-{file_name}", fnv1a(url.as_bytes()))
+}
+
+/// The 64-bit FNV-1a hash, which unlike `DefaultHasher` is stable across Rust versions and so across runs.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
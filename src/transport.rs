@@ -0,0 +1,173 @@
+use std::{
+    future::poll_fn,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures_core::Stream;
+use reqwest::{header::HeaderMap, StatusCode, Url};
+
+use crate::{CohereApiError, Endpoint};
+
+#[cfg(not(target_arch = "wasm32"))]
+type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, CohereApiError>> + Send>>;
+// Response bodies are backed by JavaScript objects on wasm, which can't be sent between threads
+#[cfg(target_arch = "wasm32")]
+type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, CohereApiError>>>>;
+
+/// A request to the API as it is handed to a [`Transport`].
+#[derive(Clone, Debug)]
+pub struct TransportRequest {
+    /// The endpoint the request is sent to.
+    pub endpoint: Endpoint,
+    /// The full URL of the endpoint.
+    pub url: Url,
+    /// All of the request's headers, including authorization.
+    pub headers: HeaderMap,
+    /// The JSON body of the request.
+    pub body: Bytes,
+}
+
+/// A response from the API as returned by a [`Transport`].
+pub struct TransportResponse {
+    /// The status code of the response.
+    pub status: StatusCode,
+    /// The headers of the response.
+    pub headers: HeaderMap,
+    /// The body of the response, which is read as it arrives for streamed responses.
+    pub body: ResponseBody,
+}
+
+/// The body of a response as a stream of chunks.
+pub struct ResponseBody(BodyStream);
+
+impl ResponseBody {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new<S: Stream<Item = Result<Bytes, CohereApiError>> + Send + 'static>(
+        stream: S,
+    ) -> Self {
+        ResponseBody(Box::pin(stream))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn new<S: Stream<Item = Result<Bytes, CohereApiError>> + 'static>(stream: S) -> Self {
+        ResponseBody(Box::pin(stream))
+    }
+
+    /// Waits for the next chunk of the body, or `None` once the whole body has been read.
+    pub async fn chunk(&mut self) -> Option<Result<Bytes, CohereApiError>> {
+        poll_fn(|cx| self.0.as_mut().poll_next(cx)).await
+    }
+
+    /// Reads the whole body.
+    pub async fn bytes(mut self) -> Result<Bytes, CohereApiError> {
+        let mut body = BytesMut::new();
+        while let Some(chunk) = self.chunk().await {
+            body.extend_from_slice(&chunk?);
+        }
+        Ok(body.freeze())
+    }
+}
+
+impl Stream for ResponseBody {
+    type Item = Result<Bytes, CohereApiError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.as_mut().poll_next(cx)
+    }
+}
+
+impl From<Bytes> for ResponseBody {
+    fn from(body: Bytes) -> Self {
+        ResponseBody::new(Chunks(vec![body].into_iter()))
+    }
+}
+
+impl From<Vec<Bytes>> for ResponseBody {
+    fn from(chunks: Vec<Bytes>) -> Self {
+        ResponseBody::new(Chunks(chunks.into_iter()))
+    }
+}
+
+/// A body whose chunks are all available up front.
+struct Chunks(std::vec::IntoIter<Bytes>);
+
+impl Stream for Chunks {
+    type Item = Result<Bytes, CohereApiError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Poll::Ready(self.0.next().map(Ok))
+    }
+}
+
+/// Sends requests to the API on behalf of the client.
+///
+/// The client sends every request through a transport after applying its rate limits,
+/// concurrency limits and middleware. [`ReqwestTransport`] is used by default.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait Transport: Send + Sync {
+    /// Sends the request and returns the response once its headers have been received.
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, CohereApiError>;
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, CohereApiError> {
+        (**self).send(request).await
+    }
+}
+
+/// A transport that sends requests with a [`reqwest::Client`].
+#[derive(Clone, Debug, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        ReqwestTransport { client }
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, CohereApiError> {
+        let response = self
+            .client
+            .post(request.url)
+            .headers(request.headers)
+            .body(request.body)
+            .send()
+            .await?;
+
+        Ok(TransportResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: ResponseBody::new(ReqwestBody(Box::pin(response.bytes_stream()))),
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type ReqwestStream = Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>;
+#[cfg(target_arch = "wasm32")]
+type ReqwestStream = Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>>>>;
+
+struct ReqwestBody(ReqwestStream);
+
+impl Stream for ReqwestBody {
+    type Item = Result<Bytes, CohereApiError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0
+            .as_mut()
+            .poll_next(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map_err(CohereApiError::from)))
+    }
+}
@@ -1,19 +1,44 @@
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use reqwest::header::HeaderValue;
+    use serde_json::json;
 
     use cohere_rust::{
+        agent::{AgentError, AgentEvent, AgentStep, AgentTool, FnTool, ToolAgent, ToolError},
         api::{
-            chat::{ChatRequest, ChatResponse, ChatStreamResponse},
+            chat::{
+                ChatMessage, ChatRequest, ChatResponse, ChatStreamResponse, SafetyMode, Tool,
+                ToolCall, ToolResult,
+            },
             classify::{Classification, ClassifyExample, ClassifyRequest, LabelProperties},
             detokenize::DetokenizeRequest,
             embed::EmbedRequest,
-            generate::{GenerateRequest, ReturnLikelihoods},
-            rerank::{ReRankModel, ReRankRequest, ReRankResult},
+            generate::{
+                GenerateRequest, GenerateResponse, GenerateStreamResponse, Generation,
+                ReturnLikelihoods,
+            },
+            rerank::{ReRankDocument, ReRankModel, ReRankOptions, ReRankRequest, ReRankResult},
             tokenize::TokenizeRequest,
-            GenerateModel, Truncate,
+            FinishReason, GenerateModel, Truncate,
         },
-        Cohere,
+        concurrency::{ConcurrencyConfig, QueueStats},
+        history::{HistoryTruncation, TokenCounter, TruncationStrategy},
+        middleware::{Middleware, MiddlewareRequest, MiddlewareResponse},
+        rate_limit::{RateLimit, RateLimitConfig},
+        session::ChatSession,
+        testing::{
+            FakeFailure, FakeServer, InMemoryResponse, InMemoryTransport, MockCall, MockCohere,
+            RecordingTransport, ReplayTransport, FAKE_EMBEDDING_DIMENSIONS,
+        },
+        Cohere, CohereApi, CohereApiError, CohereRequestError, CohereStreamError, Endpoint,
     };
 
     #[tokio::test]
@@ -74,52 +99,54 @@ mod tests {
 
         let response = client
             .classify(&ClassifyRequest {
-                inputs: &[
+                inputs: vec![
                     "Confirm your email address".to_string(),
                     "hey i need u to send some $".to_string(),
-                ],
-                examples: &vec![
+                ]
+                .into(),
+                examples: vec![
                     ClassifyExample {
-                        text: "Dermatologists don't like her!",
-                        label: "Spam",
+                        text: "Dermatologists don't like her!".into(),
+                        label: "Spam".into(),
                     },
                     ClassifyExample {
-                        text: "Hello, open to this?",
-                        label: "Spam",
+                        text: "Hello, open to this?".into(),
+                        label: "Spam".into(),
                     },
                     ClassifyExample {
-                        text: "I need help please wire me $1000 right now",
-                        label: "Spam",
+                        text: "I need help please wire me $1000 right now".into(),
+                        label: "Spam".into(),
                     },
                     ClassifyExample {
-                        text: "Nice to know you ;)",
-                        label: "Spam",
+                        text: "Nice to know you ;)".into(),
+                        label: "Spam".into(),
                     },
                     ClassifyExample {
-                        text: "Please help me?",
-                        label: "Spam",
+                        text: "Please help me?".into(),
+                        label: "Spam".into(),
                     },
                     ClassifyExample {
-                        text: "Your parcel will be delivered today",
-                        label: "Not spam",
+                        text: "Your parcel will be delivered today".into(),
+                        label: "Not spam".into(),
                     },
                     ClassifyExample {
-                        text: "Review changes to our Terms and Conditions",
-                        label: "Not spam",
+                        text: "Review changes to our Terms and Conditions".into(),
+                        label: "Not spam".into(),
                     },
                     ClassifyExample {
-                        text: "Weekly sync notes",
-                        label: "Not spam",
+                        text: "Weekly sync notes".into(),
+                        label: "Not spam".into(),
                     },
                     ClassifyExample {
-                        text: "Re: Follow up from today's meeting",
-                        label: "Not spam",
+                        text: "Re: Follow up from today's meeting".into(),
+                        label: "Not spam".into(),
                     },
                     ClassifyExample {
-                        text: "Pre-read for tomorrow",
-                        label: "Not spam",
+                        text: "Pre-read for tomorrow".into(),
+                        label: "Not spam".into(),
                     },
-                ],
+                ]
+                .into(),
                 ..Default::default()
             })
             .await;
@@ -209,7 +236,7 @@ mod tests {
         let client = Cohere::new(mock_url, "test-key");
 
         let request = DetokenizeRequest {
-            tokens: &[10104, 12221, 1315, 34, 1420, 69],
+            tokens: vec![10104, 12221, 1315, 34, 1420, 69].into(),
             model: Some(GenerateModel::CommandNightly),
         };
 
@@ -276,7 +303,7 @@ mod tests {
 
         let request = EmbedRequest {
             model: None,
-            texts: &["hi".to_string()],
+            texts: vec!["hi".to_string()].into(),
             truncate: Truncate::End,
         };
 
@@ -332,7 +359,7 @@ mod tests {
                 max_tokens: Some(20),
                 return_likelihoods: Some(ReturnLikelihoods::None),
                 truncate: Some(Truncate::End),
-                prompt: "Once upon a time in a magical land called",
+                prompt: "Once upon a time in a magical land called".into(),
                 ..Default::default()
             })
             .await;
@@ -349,6 +376,219 @@ mod tests {
         assert_eq!(" Silicon Valley, there was a very unusual sight: An actual new idea. It was a strange sight".to_string(), response[0].text);
     }
 
+    #[tokio::test]
+    async fn test_generate_finish_reason() {
+        // Create mock server
+        let mut mock_server = mockito::Server::new_async().await;
+        let mock_url = mock_server.url();
+
+        // Create a mock
+        let mock_endpoint = mock_server
+            .mock("POST", "/generate")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{
+                    "id": "65e5ecf2-0872-45d2-b15c-f59647273e97",
+                    "generations": [
+                      {
+                        "id": "ef047b8a-0231-40e1-9f58-af7b135ce7d7",
+                        "text": " Silicon Valley",
+                        "finish_reason": "MAX_TOKENS"
+                      },
+                      {
+                        "id": "0c9cb118-f841-4588-b835-f9a4fe2c572e",
+                        "text": " Narnia"
+                      }
+                    ]
+                  }"#,
+            )
+            .create_async()
+            .await;
+
+        let client = Cohere::new(mock_url, "test-key");
+
+        let response = client
+            .generate(&GenerateRequest {
+                max_tokens: Some(2),
+                prompt: "Once upon a time in a magical land called".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // assert that mock endpoint was called
+        mock_endpoint.assert_async().await;
+
+        // the finish reason is only set when the API returns it
+        assert_eq!(Some(FinishReason::MaxTokens), response[0].finish_reason);
+        assert_eq!(None, response[1].finish_reason);
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream() {
+        // Create mock server
+        let mut mock_server = mockito::Server::new_async().await;
+        let mock_url = mock_server.url();
+
+        let mock_generate_stream = [
+            "{\"text\":\" Silicon\",\"is_finished\":false,\"event_type\":\"text-generation\"}\n",
+            "{\"text\":\" Valley\",\"is_finished\":false,\"event_type\":\"text-generation\"}\n",
+            "{\"is_finished\":true,\"event_type\":\"stream-end\",\"finish_reason\":\"COMPLETE\",\"response\":{\"id\":\"65e5ecf2-0872-45d2-b15c-f59647273e97\",\"generations\":[{\"id\":\"ef047b8a-0231-40e1-9f58-af7b135ce7d7\",\"text\":\" Silicon Valley\",\"finish_reason\":\"COMPLETE\"}],\"prompt\":\"Once upon a time in a magical land called\"}}\n",
+        ];
+
+        // Create a mock
+        let mock_endpoint = mock_server
+            .mock("POST", "/generate")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"stream": true}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_chunked_body(move |w| {
+                for chunk in mock_generate_stream.iter() {
+                    w.write_all(chunk.as_bytes()).unwrap();
+                }
+                Ok(())
+            })
+            .create_async()
+            .await;
+
+        let client = Cohere::new(mock_url, "test-key");
+
+        let response = client
+            .generate_stream(&GenerateRequest {
+                max_tokens: Some(20),
+                prompt: "Once upon a time in a magical land called".into(),
+                ..Default::default()
+            })
+            .await;
+
+        // assert that mock endpoint was called
+        mock_endpoint.assert_async().await;
+
+        assert!(response.is_ok());
+
+        let mut stream = response.unwrap();
+        let expected_messages = [
+            GenerateStreamResponse::GenerateTextGeneration {
+                index: None,
+                is_finished: false,
+                text: " Silicon".to_string(),
+            },
+            GenerateStreamResponse::GenerateTextGeneration {
+                index: None,
+                is_finished: false,
+                text: " Valley".to_string(),
+            },
+            GenerateStreamResponse::GenerateStreamEnd {
+                finish_reason: FinishReason::Complete,
+                is_finished: true,
+                response: GenerateResponse {
+                    id: "65e5ecf2-0872-45d2-b15c-f59647273e97".to_string(),
+                    generations: vec![Generation {
+                        text: " Silicon Valley".to_string(),
+                        likelihood: 0.0,
+                        token_likelihoods: vec![],
+                        finish_reason: Some(FinishReason::Complete),
+                    }],
+                },
+            },
+        ];
+
+        let mut count: usize = 0;
+        while let Some(message) = stream.recv().await {
+            assert!(message.is_ok());
+            assert_eq!(expected_messages[count], message.unwrap());
+            count += 1;
+        }
+        assert_eq!(expected_messages.len(), count);
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_generations() {
+        // Create mock server
+        let mut mock_server = mockito::Server::new_async().await;
+        let mock_url = mock_server.url();
+
+        // the text of both generations is interleaved
+        let mock_generate_stream = [
+            "{\"index\":0,\"text\":\" Silicon\",\"is_finished\":false,\"event_type\":\"text-generation\"}\n",
+            "{\"index\":1,\"text\":\" Narnia\",\"is_finished\":false,\"event_type\":\"text-generation\"}\n",
+            "{\"index\":0,\"text\":\" Valley\",\"is_finished\":false,\"event_type\":\"text-generation\"}\n",
+            "{\"is_finished\":true,\"event_type\":\"stream-end\",\"finish_reason\":\"COMPLETE\",\"response\":{\"id\":\"65e5ecf2-0872-45d2-b15c-f59647273e97\",\"generations\":[{\"id\":\"ef047b8a-0231-40e1-9f58-af7b135ce7d7\",\"text\":\" Silicon Valley\",\"finish_reason\":\"COMPLETE\"},{\"id\":\"0b9c2f1e-7d4a-4c5e-9a3b-2f6e8d1c4b7a\",\"text\":\" Narnia\",\"finish_reason\":\"COMPLETE\"}]}}\n",
+        ];
+
+        // Create a mock
+        let mock_endpoint = mock_server
+            .mock("POST", "/generate")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"stream": true, "num_generations": 2}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_chunked_body(move |w| {
+                for chunk in mock_generate_stream.iter() {
+                    w.write_all(chunk.as_bytes()).unwrap();
+                }
+                Ok(())
+            })
+            .create_async()
+            .await;
+
+        let client = Cohere::new(mock_url, "test-key");
+
+        let mut stream = client
+            .generate_stream(&GenerateRequest {
+                prompt: "Once upon a time in a magical land called".into(),
+                num_generations: Some(2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // assert that mock endpoint was called
+        mock_endpoint.assert_async().await;
+
+        let mut texts = vec![String::new(); 2];
+        let mut generations = vec![];
+        while let Some(message) = stream.recv().await {
+            match message.unwrap() {
+                GenerateStreamResponse::GenerateTextGeneration { index, text, .. } => {
+                    texts[index.unwrap() as usize].push_str(&text);
+                }
+                GenerateStreamResponse::GenerateStreamEnd { response, .. } => {
+                    generations = response.generations;
+                }
+            }
+        }
+
+        assert_eq!(vec![" Silicon Valley", " Narnia"], texts);
+        assert_eq!(
+            texts,
+            generations
+                .into_iter()
+                .map(|generation| generation.text)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_finish_reason() {
+        let reasons: Vec<FinishReason> =
+            serde_json::from_str(r#"["COMPLETE", "ERROR_TOXIC", "SOMETHING_NEW"]"#).unwrap();
+
+        assert_eq!(
+            vec![
+                FinishReason::Complete,
+                FinishReason::ErrorToxic,
+                FinishReason::Other("SOMETHING_NEW".to_string())
+            ],
+            reasons
+        );
+        assert_eq!("SOMETHING_NEW", reasons[2].to_string());
+    }
+
     #[tokio::test]
     async fn test_chat() {
         // Create mock server
@@ -383,7 +623,7 @@ mod tests {
 
         let response = client
             .chat(&ChatRequest {
-                message: "who wrote the book where is my cheese?",
+                message: "who wrote the book where is my cheese?".into(),
                 ..Default::default()
             })
             .await;
@@ -420,12 +660,14 @@ mod tests {
                 text: ".".to_string(),
             },
             ChatStreamResponse::ChatStreamEnd {
-                finish_reason: "COMPLETE".to_string(),
+                finish_reason: FinishReason::Complete,
                 is_finished: true,
                 response: ChatResponse {
                     generation_id: "0c9cb118-f841-4588-b835-f9a4fe2c572e".to_string(),
                     response_id: "feab94ed-789b-42f2-8f4f-c49d56d28734".to_string(),
                     text: "Thomas P. Frank.".to_string(),
+                    prompt: None,
+                    tool_calls: Vec::new(),
                 },
             },
         ];
@@ -439,6 +681,101 @@ mod tests {
         assert_eq!(expected_messages.len(), count);
     }
 
+    #[tokio::test]
+    async fn test_chat_parameters() {
+        // Create mock server
+        let mut mock_server = mockito::Server::new_async().await;
+        let mock_url = mock_server.url();
+
+        let mock_chat_stream = [
+            "{\"is_finished\":false,\"event_type\":\"stream-start\",\"generation_id\":\"0c9cb118-f841-4588-b835-f9a4fe2c572e\"}\n",
+            "{\"is_finished\":true,\"event_type\":\"stream-end\",\"response\":{\"response_id\":\"feab94ed-789b-42f2-8f4f-c49d56d28734\",\"text\":\"Spencer Johnson.\",\"prompt\":\"User: who wrote the book where is my cheese?\",\"generation_id\":\"0c9cb118-f841-4588-b835-f9a4fe2c572e\"},\"finish_reason\":\"COMPLETE\"}\n",
+        ];
+
+        // Create a mock
+        let mock_endpoint = mock_server
+            .mock("POST", "/chat")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "k": 10,
+                "p": 0.5,
+                "seed": 42,
+                "stop_sequences": ["END"],
+                "frequency_penalty": 0.25,
+                "presence_penalty": 0.5,
+                "raw_prompting": false,
+                "safety_mode": "STRICT",
+                "return_prompt": true,
+                "stream": true
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_chunked_body(move |w| {
+                for chunk in mock_chat_stream.iter() {
+                    w.write_all(chunk.as_bytes()).unwrap();
+                }
+                Ok(())
+            })
+            .create_async()
+            .await;
+
+        let client = Cohere::new(mock_url, "test-key");
+
+        let request = ChatRequest::builder()
+            .message("who wrote the book where is my cheese?")
+            .k(10u64)
+            .p(0.5)
+            .seed(42u64)
+            .stop_sequences(vec!["END".to_string()])
+            .frequency_penalty(0.25)
+            .presence_penalty(0.5)
+            .raw_prompting(false)
+            .safety_mode(SafetyMode::Strict)
+            .return_prompt(true)
+            .build()
+            .unwrap();
+        let mut stream = client.chat(&request).await.unwrap();
+
+        // assert that mock endpoint was called
+        mock_endpoint.assert_async().await;
+
+        assert!(matches!(
+            stream.recv().await,
+            Some(Ok(ChatStreamResponse::ChatStreamStart { .. }))
+        ));
+        match stream.recv().await {
+            Some(Ok(ChatStreamResponse::ChatStreamEnd { response, .. })) => assert_eq!(
+                Some("User: who wrote the book where is my cheese?".to_string()),
+                response.prompt
+            ),
+            message => panic!("unexpected message {message:?}"),
+        }
+        assert!(stream.recv().await.is_none());
+
+        // penalties are validated like the other sampling parameters
+        assert_eq!(
+            CohereRequestError::InvalidParameter(
+                "frequency_penalty",
+                "must be between 0 and 1, got 1.5".to_string()
+            ),
+            ChatRequest::builder()
+                .message("hi")
+                .frequency_penalty(1.5)
+                .build()
+                .unwrap_err()
+        );
+        assert_eq!(
+            CohereRequestError::InvalidParameter(
+                "presence_penalty",
+                "must be between 0 and 1, got -0.5".to_string()
+            ),
+            ChatRequest::builder()
+                .message("hi")
+                .presence_penalty(-0.5)
+                .build()
+                .unwrap_err()
+        );
+    }
+
     #[tokio::test]
     async fn test_tokenize() {
         // Create mock server
@@ -481,7 +818,7 @@ mod tests {
         let client = Cohere::new(mock_url, "test-key");
 
         let request = TokenizeRequest {
-            text: "tokenize me! :D",
+            text: "tokenize me! :D".into(),
             model: Some(GenerateModel::CommandNightly),
         };
 
@@ -554,8 +891,8 @@ mod tests {
         ];
 
         let request = ReRankRequest {
-            query: "What is the capital of the United States?",
-            documents: &documents.map(|d| d.to_string()),
+            query: "What is the capital of the United States?".into(),
+            documents: documents.map(ReRankDocument::from).to_vec().into(),
             model: ReRankModel::EnglishV2,
             top_n: Some(4),
             ..Default::default()
@@ -578,25 +915,2295 @@ mod tests {
             vec![
                 ReRankResult {
                     index: 2,
-                    relevance_score: 0.98005307
+                    relevance_score: 0.98005307,
+                    document: None,
                 },
                 ReRankResult {
                     index: 3,
-                    relevance_score: 0.27904198
+                    relevance_score: 0.27904198,
+                    document: None,
                 },
                 ReRankResult {
                     index: 0,
-                    relevance_score: 0.10194652
+                    relevance_score: 0.10194652,
+                    document: None,
                 },
                 ReRankResult {
                     index: 1,
-                    relevance_score: 0.0721122
+                    relevance_score: 0.0721122,
+                    document: None,
                 }
             ],
             response
         );
     }
 
+    #[tokio::test]
+    async fn test_owned_request() {
+        // Create mock server
+        let mut mock_server = mockito::Server::new_async().await;
+        let mock_url = mock_server.url();
+
+        // Create a mock
+        let mock_endpoint = mock_server
+            .mock("POST", "/tokenize")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "text": "tokenize me! :D",
+                "model": "my-custom-model"
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"tokens": [34160], "token_strings": ["token"]}"#)
+            .create_async()
+            .await;
+
+        let client = Cohere::new(mock_url, "test-key");
+
+        let request: TokenizeRequest<'static> = {
+            let text = String::from("tokenize me! :D");
+            TokenizeRequest {
+                text: text.as_str().into(),
+                model: Some(GenerateModel::Custom("my-custom-model".to_string())),
+            }
+            .into_owned()
+        };
+
+        // round-trip through JSON as if the request was persisted to a queue
+        let persisted = serde_json::to_string(&request).unwrap();
+        let request: TokenizeRequest = serde_json::from_str(&persisted).unwrap();
+
+        let response = tokio::spawn(async move { client.tokenize(&request).await })
+            .await
+            .unwrap();
+
+        // assert that mock endpoint was called
+        mock_endpoint.assert_async().await;
+
+        assert!(response.is_ok());
+        assert_eq!(vec![34160], response.unwrap().tokens);
+    }
+
+    #[test]
+    fn test_custom_models() {
+        use cohere_rust::api::EmbedModel;
+
+        // custom models are sent as their bare ID rather than as a tagged object
+        assert_eq!(
+            json!("my-generate-model"),
+            serde_json::to_value(GenerateModel::Custom("my-generate-model".to_string())).unwrap()
+        );
+        assert_eq!(
+            json!("my-embed-model"),
+            serde_json::to_value(EmbedModel::Custom("my-embed-model".to_string())).unwrap()
+        );
+
+        // known model IDs are read back as the known models and any other ID as a custom model
+        assert!(matches!(
+            serde_json::from_value(json!("command-r")).unwrap(),
+            GenerateModel::CommandR
+        ));
+        assert!(matches!(
+            serde_json::from_value(json!("my-generate-model")).unwrap(),
+            GenerateModel::Custom(id) if id == "my-generate-model"
+        ));
+        assert!(matches!(
+            serde_json::from_value(json!("embed-english-v3.0")).unwrap(),
+            EmbedModel::EnglishV3
+        ));
+        assert!(matches!(
+            serde_json::from_value(json!("my-embed-model")).unwrap(),
+            EmbedModel::Custom(id) if id == "my-embed-model"
+        ));
+    }
+
+    #[test]
+    fn test_request_builders() {
+        let request = ChatRequest::builder()
+            .message("who wrote the book where is my cheese?")
+            .model(GenerateModel::CommandR)
+            .temperature(0.3)
+            .p(1.0)
+            .build();
+
+        assert!(request.is_ok());
+
+        let request = request.unwrap();
+
+        assert_eq!("who wrote the book where is my cheese?", request.message);
+        assert_eq!(Some(0.3), request.temperature);
+
+        assert_eq!(
+            CohereRequestError::MissingField("message"),
+            ChatRequest::builder().temperature(0.3).build().unwrap_err()
+        );
+        assert!(matches!(
+            ChatRequest::builder()
+                .message("hi")
+                .temperature(-1.0)
+                .build(),
+            Err(CohereRequestError::InvalidParameter("temperature", _))
+        ));
+        assert!(matches!(
+            ChatRequest::builder().message("hi").p(0.0).build(),
+            Err(CohereRequestError::InvalidParameter("p", _))
+        ));
+        assert!(matches!(
+            GenerateRequest::builder()
+                .prompt("Once upon a time")
+                .num_generations(6)
+                .build(),
+            Err(CohereRequestError::InvalidParameter("num_generations", _))
+        ));
+        assert!(matches!(
+            GenerateRequest::builder()
+                .prompt("Once upon a time")
+                .logit_bias(HashMap::from([(11, 10.5)]))
+                .build(),
+            Err(CohereRequestError::InvalidParameter("logit_bias", _))
+        ));
+        assert!(matches!(
+            GenerateRequest::builder()
+                .prompt("Once upon a time")
+                .frequency_penalty(2.0)
+                .build(),
+            Err(CohereRequestError::InvalidParameter("frequency_penalty", _))
+        ));
+        assert_eq!(
+            CohereRequestError::MissingField("documents"),
+            ReRankRequest::builder()
+                .query("What is the capital of the United States?")
+                .build()
+                .unwrap_err()
+        );
+        assert!(EmbedRequest::builder()
+            .texts(vec!["hi".to_string()])
+            .build()
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        // Create mock server
+        let mut mock_server = mockito::Server::new_async().await;
+        let mock_url = mock_server.url();
+
+        // Create a mock
+        let mock_endpoint = mock_server
+            .mock("POST", "/tokenize")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"tokens": [34160], "token_strings": ["token"]}"#)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let client = Cohere::builder()
+            .api_url(mock_url)
+            .api_key("test-key")
+            .rate_limits(RateLimitConfig {
+                endpoints: HashMap::from([(
+                    Endpoint::Tokenize,
+                    RateLimit {
+                        requests: 1,
+                        period: Duration::from_millis(200),
+                    },
+                )]),
+                ..Default::default()
+            })
+            .build();
+
+        let request = TokenizeRequest {
+            text: "token".into(),
+            model: None,
+        };
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(client.tokenize(&request).await.is_ok());
+        }
+
+        // assert that mock endpoint was called
+        mock_endpoint.assert_async().await;
+
+        // the first request is let through immediately and the next two wait for the bucket to refill
+        assert!(start.elapsed() >= Duration::from_millis(400));
+
+        // a request waiting on its endpoint's limit doesn't use up the client limit
+        let mock_embed = mock_server
+            .mock("POST", "/embed")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id": "1", "texts": ["hi"], "embeddings": [[0.5]]}"#)
+            .create_async()
+            .await;
+        let client = Cohere::builder()
+            .api_url(mock_server.url())
+            .api_key("test-key")
+            .rate_limits(RateLimitConfig {
+                client: Some(RateLimit {
+                    requests: 2,
+                    period: Duration::from_secs(2),
+                }),
+                endpoints: HashMap::from([(Endpoint::Tokenize, RateLimit::per_second(1))]),
+                ..Default::default()
+            })
+            .build();
+        let embed = EmbedRequest {
+            texts: vec!["hi".to_string()].into(),
+            ..Default::default()
+        };
+
+        assert!(client.tokenize(&request).await.is_ok());
+        tokio::select! {
+            _ = client.tokenize(&request) => panic!("the tokenize limit should hold up the request"),
+            elapsed = async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                let start = Instant::now();
+                assert!(client.embed(&embed).await.is_ok());
+                start.elapsed()
+            } => assert!(elapsed < Duration::from_millis(500)),
+        }
+        mock_embed.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_rate_limit() {
+        // Create mock server
+        let mut mock_server = mockito::Server::new_async().await;
+        let mock_url = mock_server.url();
+
+        // Create a mock
+        let mock_endpoint = mock_server
+            .mock("POST", "/embed")
+            .with_status(429)
+            .with_header("content-type", "application/json")
+            .with_header("retry-after", "0.5")
+            .with_body(r#"{"message":"too many requests"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let client = Cohere::builder()
+            .api_url(mock_url)
+            .api_key("test-key")
+            .rate_limits(RateLimitConfig {
+                client: Some(RateLimit::per_second(100)),
+                adaptive: true,
+                ..Default::default()
+            })
+            .build();
+
+        let request = EmbedRequest {
+            texts: vec!["hi".to_string()].into(),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        assert!(client.embed(&request).await.is_err());
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(client.embed(&request).await.is_err());
+
+        // assert that mock endpoint was called
+        mock_endpoint.assert_async().await;
+
+        // the second request waits for the delay requested by the API
+        assert!(start.elapsed() >= Duration::from_millis(500));
+
+        // the delay can also be given as the date to retry after
+        let retry_after = std::time::SystemTime::now() + Duration::from_secs(2);
+        let mock_endpoint = mock_server
+            .mock("POST", "/tokenize")
+            .with_status(429)
+            .with_header("content-type", "application/json")
+            .with_header("retry-after", &httpdate::fmt_http_date(retry_after))
+            .with_body(r#"{"message":"too many requests"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let request = TokenizeRequest {
+            text: "token".into(),
+            model: None,
+        };
+
+        let start = Instant::now();
+        assert!(client.tokenize(&request).await.is_err());
+        assert!(client.tokenize(&request).await.is_err());
+        mock_endpoint.assert_async().await;
+        // the date is rounded down to the second
+        assert!(start.elapsed() >= Duration::from_secs(1));
+
+        // requests are paused even without any configured limits
+        let mock_endpoint = mock_server
+            .mock("POST", "/detokenize")
+            .with_status(429)
+            .with_header("content-type", "application/json")
+            .with_header("retry-after", "0.5")
+            .with_body(r#"{"message":"too many requests"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let client = Cohere::builder()
+            .api_url(mock_server.url())
+            .api_key("test-key")
+            .rate_limits(RateLimitConfig {
+                adaptive: true,
+                ..Default::default()
+            })
+            .build();
+        let request = DetokenizeRequest {
+            tokens: vec![10104].into(),
+            model: None,
+        };
+
+        let start = Instant::now();
+        assert!(client.detokenize(&request).await.is_err());
+        assert!(client.detokenize(&request).await.is_err());
+        mock_endpoint.assert_async().await;
+        assert!(start.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit() {
+        // Create mock server
+        let mut mock_server = mockito::Server::new_async().await;
+        let mock_url = mock_server.url();
+
+        // Create a mock
+        let mock_endpoint = mock_server
+            .mock("POST", "/rerank")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_chunked_body(|w| {
+                std::thread::sleep(Duration::from_millis(200));
+                w.write_all(br#"{"results": [{"index": 0, "relevance_score": 0.5}]}"#)
+            })
+            .expect(2)
+            .create_async()
+            .await;
+
+        let client = Cohere::builder()
+            .api_url(mock_url)
+            .api_key("test-key")
+            .concurrency(ConcurrencyConfig {
+                endpoints: HashMap::from([(Endpoint::Rerank, 1)]),
+                max_queue_size: Some(1),
+                ..Default::default()
+            })
+            .build();
+
+        let request = ReRankRequest {
+            query: "What is the capital of the United States?".into(),
+            documents: vec!["Washington, D.C.".into()].into(),
+            ..Default::default()
+        };
+
+        let stats = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            client.concurrency_stats()
+        };
+
+        let (first, second, third, stats) = tokio::join!(
+            client.rerank(&request),
+            client.rerank(&request),
+            client.rerank(&request),
+            stats
+        );
+
+        // assert that mock endpoint was called
+        mock_endpoint.assert_async().await;
+
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        assert!(matches!(third, Err(CohereApiError::QueueFull)));
+        assert_eq!(
+            Some(&QueueStats {
+                in_flight: 1,
+                queued: 1,
+                max_in_flight: 1
+            }),
+            stats.endpoints.get(&Endpoint::Rerank)
+        );
+        assert_eq!(
+            Some(&QueueStats {
+                in_flight: 0,
+                queued: 0,
+                max_in_flight: 1
+            }),
+            client.concurrency_stats().endpoints.get(&Endpoint::Rerank)
+        );
+
+        // a request held up by the rate limits isn't in flight, so it doesn't hold up other requests
+        let mock_tokenize = mock_server
+            .mock("POST", "/tokenize")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"tokens": [34160], "token_strings": ["token"]}"#)
+            .create_async()
+            .await;
+        let client = Cohere::builder()
+            .api_url(mock_server.url())
+            .api_key("test-key")
+            .rate_limits(RateLimitConfig {
+                endpoints: HashMap::from([(Endpoint::Tokenize, RateLimit::per_second(1))]),
+                ..Default::default()
+            })
+            .concurrency(ConcurrencyConfig {
+                max_in_flight: Some(1),
+                ..Default::default()
+            })
+            .build();
+        let tokenize = TokenizeRequest {
+            text: "token".into(),
+            model: None,
+        };
+
+        assert!(client.tokenize(&tokenize).await.is_ok());
+        tokio::select! {
+            _ = client.tokenize(&tokenize) => panic!("the tokenize limit should hold up the request"),
+            elapsed = async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                let start = Instant::now();
+                assert!(client.rerank(&request).await.is_ok());
+                start.elapsed()
+            } => assert!(elapsed < Duration::from_millis(500)),
+        }
+        mock_tokenize.assert_async().await;
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_queue_depth_metrics() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        // The recorder is only used on this thread, so the requests of the tests running alongside aren't recorded
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let queue_depths = || {
+            snapshotter
+                .snapshot()
+                .into_vec()
+                .into_iter()
+                .filter(|(key, _, _, _)| key.key().name() == "cohere_queue_depth")
+                .map(|(key, _, _, value)| {
+                    let limit = key
+                        .key()
+                        .labels()
+                        .map(|label| label.value().to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    match value {
+                        DebugValue::Gauge(depth) => (limit, depth.0),
+                        value => panic!("expected a gauge, got {value:?}"),
+                    }
+                })
+                .collect::<HashMap<_, _>>()
+        };
+
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                // Create mock server
+                let mut mock_server = mockito::Server::new_async().await;
+                let mock_url = mock_server.url();
+
+                // Create a mock
+                let mock_endpoint = mock_server
+                    .mock("POST", "/rerank")
+                    .with_status(200)
+                    .with_header("content-type", "application/json")
+                    .with_chunked_body(|w| {
+                        std::thread::sleep(Duration::from_millis(200));
+                        w.write_all(br#"{"results": [{"index": 0, "relevance_score": 0.5}]}"#)
+                    })
+                    .expect(3)
+                    .create_async()
+                    .await;
+
+                let client = Cohere::builder()
+                    .api_url(mock_url)
+                    .api_key("test-key")
+                    .concurrency(ConcurrencyConfig {
+                        max_in_flight: Some(2),
+                        endpoints: HashMap::from([(Endpoint::Rerank, 1)]),
+                        ..Default::default()
+                    })
+                    .build();
+
+                let request = ReRankRequest {
+                    query: "What is the capital of the United States?".into(),
+                    documents: vec!["Washington, D.C.".into()].into(),
+                    ..Default::default()
+                };
+
+                let depths = async {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    queue_depths()
+                };
+                let (first, second, third, depths) = tokio::join!(
+                    client.rerank(&request),
+                    client.rerank(&request),
+                    client.rerank(&request),
+                    depths
+                );
+
+                // assert that mock endpoint was called
+                mock_endpoint.assert_async().await;
+
+                assert!(first.is_ok() && second.is_ok() && third.is_ok());
+                // the endpoint limit lets one request through and queues the others
+                assert_eq!(Some(&2.0), depths.get("endpoint rerank"));
+            })
+        });
+
+        // snapshots reset the gauge, so both queued requests are recorded as having left the queue since
+        assert_eq!(Some(&-2.0), queue_depths().get("endpoint rerank"));
+    }
+
+    #[derive(Default)]
+    struct TestMiddleware {
+        cache: Mutex<HashMap<String, Bytes>>,
+        statuses: Mutex<Vec<u16>>,
+        errors: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Middleware for TestMiddleware {
+        async fn on_request(&self, request: &mut MiddlewareRequest) -> Option<Bytes> {
+            request
+                .headers
+                .insert("X-Tenant", HeaderValue::from_static("tenant-a"));
+            self.cache
+                .lock()
+                .unwrap()
+                .get(&request.body.to_string())
+                .cloned()
+        }
+
+        async fn on_response(&self, request: &MiddlewareRequest, response: &MiddlewareResponse) {
+            self.statuses.lock().unwrap().push(response.status.as_u16());
+            if let (true, Some(body)) = (response.status.is_success(), &response.body) {
+                self.cache
+                    .lock()
+                    .unwrap()
+                    .insert(request.body.to_string(), body.clone());
+            }
+        }
+
+        async fn on_error(&self, _request: &MiddlewareRequest, error: &CohereApiError) {
+            self.errors.lock().unwrap().push(error.to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware() {
+        // Create mock server
+        let mut mock_server = mockito::Server::new_async().await;
+        let mock_url = mock_server.url();
+
+        // Create a mock
+        let mock_endpoint = mock_server
+            .mock("POST", "/detokenize")
+            .match_header("X-Tenant", "tenant-a")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "detokenized! :D"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let mock_failure = mock_server
+            .mock("POST", "/tokenize")
+            .with_status(400)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"message":"invalid request: text cannot be empty"}"#)
+            .create_async()
+            .await;
+
+        let middleware = Arc::new(TestMiddleware::default());
+
+        let client = Cohere::builder()
+            .api_url(mock_url)
+            .api_key("test-key")
+            .middleware(middleware.clone())
+            .build();
+
+        let request = DetokenizeRequest {
+            tokens: vec![10104, 12221].into(),
+            model: None,
+        };
+
+        // the second request is served from the cache
+        for _ in 0..2 {
+            let response = client.detokenize(&request).await;
+            assert!(response.is_ok());
+            assert_eq!("detokenized! :D".to_string(), response.unwrap());
+        }
+
+        let response = client
+            .tokenize(&TokenizeRequest {
+                text: "".into(),
+                model: None,
+            })
+            .await;
+        assert!(response.is_err());
+
+        // assert that mock endpoints were called
+        mock_endpoint.assert_async().await;
+        mock_failure.assert_async().await;
+
+        assert_eq!(vec![200, 400], *middleware.statuses.lock().unwrap());
+        assert_eq!(
+            vec!["API request failed with status code `400 Bad Request` and error message `invalid request: text cannot be empty`".to_string()],
+            *middleware.errors.lock().unwrap()
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    struct SpanRecorder(Arc<Mutex<HashMap<String, String>>>);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::field::Visit for SpanRecorder {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanRecorder {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            attrs.record(&mut self.clone());
+        }
+
+        fn on_record(
+            &self,
+            _id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            values.record(&mut self.clone());
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn test_tracing() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        // Create mock server
+        let mut mock_server = mockito::Server::new_async().await;
+        let mock_url = mock_server.url();
+
+        // Create a mock
+        let mock_endpoint = mock_server
+            .mock("POST", "/embed")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_header("x-request-id", "req-123")
+            .with_body(
+                r#"{
+                    "id": "3915734e-bd8e-4ff4-9be1-f6dfd29dc386",
+                    "embeddings": [[1.0, 2.0]],
+                    "meta": {"billed_units": {"input_tokens": 3}}
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let client = Cohere::new(mock_url, "test-key");
+
+        let recorder = SpanRecorder::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+        let response = client
+            .embed(&EmbedRequest {
+                model: Some(cohere_rust::api::EmbedModel::EnglishV3),
+                texts: vec!["hi".to_string()].into(),
+                truncate: Truncate::End,
+            })
+            .await;
+
+        // assert that mock endpoint was called
+        mock_endpoint.assert_async().await;
+
+        assert!(response.is_ok());
+
+        let fields = recorder.0.lock().unwrap();
+        assert_eq!("embeddings embed-english-v3.0", fields["otel.name"]);
+        assert_eq!("cohere", fields["gen_ai.system"]);
+        assert_eq!("embeddings", fields["gen_ai.operation.name"]);
+        assert_eq!("embed-english-v3.0", fields["gen_ai.request.model"]);
+        assert_eq!(
+            "3915734e-bd8e-4ff4-9be1-f6dfd29dc386",
+            fields["gen_ai.response.id"]
+        );
+        assert_eq!("3", fields["gen_ai.usage.input_tokens"]);
+        assert_eq!("200", fields["http.response.status_code"]);
+        assert_eq!("req-123", fields["cohere.request_id"]);
+        assert!(fields.contains_key("latency_ms"));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        // The recorder is only used on this thread, so the requests of the tests running alongside aren't recorded
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                // Create mock server
+                let mut mock_server = mockito::Server::new_async().await;
+                let mock_url = mock_server.url();
+
+                let mock_chat_stream = [
+                    "{\"is_finished\":false,\"event_type\":\"stream-start\",\"generation_id\":\"0c9cb118-f841-4588-b835-f9a4fe2c572e\"}\n",
+                    "{\"is_finished\":false,\"event_type\":\"text-generation\",\"text\":\"Thomas P. Frank.\"}\n",
+                    "{\"is_finished\":true,\"event_type\":\"stream-end\",\"response\":{\"response_id\":\"feab94ed-789b-42f2-8f4f-c49d56d28734\",\"text\":\"Thomas P. Frank.\",\"generation_id\":\"0c9cb118-f841-4588-b835-f9a4fe2c572e\",\"meta\":{\"billed_units\":{\"input_tokens\":12,\"output_tokens\":5}}},\"finish_reason\":\"COMPLETE\"}\n",
+                ];
+
+                // Create a mock
+                let mock_endpoint = mock_server
+                    .mock("POST", "/chat")
+                    .with_status(200)
+                    .with_header("content-type", "application/json")
+                    .with_chunked_body(move |w| {
+                        for chunk in mock_chat_stream.iter() {
+                            w.write_all(chunk.as_bytes()).unwrap();
+                        }
+                        Ok(())
+                    })
+                    .create_async()
+                    .await;
+
+                let client = Cohere::new(mock_url, "test-key");
+
+                let response = client
+                    .chat(&ChatRequest {
+                        message: "who wrote the book where is my cheese?".into(),
+                        model: Some(GenerateModel::CommandR),
+                        ..Default::default()
+                    })
+                    .await;
+
+                // assert that mock endpoint was called
+                mock_endpoint.assert_async().await;
+
+                let mut stream = response.unwrap();
+                while stream.recv().await.is_some() {}
+            })
+        });
+
+        let metrics: HashMap<String, DebugValue> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                assert!(key
+                    .labels()
+                    .any(|label| label.key() == "model" && label.value() == "command-r"));
+                (key.name().to_string(), value)
+            })
+            .collect();
+
+        assert_eq!(
+            Some(&DebugValue::Counter(1)),
+            metrics.get("cohere_requests_total")
+        );
+        assert_eq!(
+            Some(&DebugValue::Counter(12)),
+            metrics.get("cohere_input_tokens_total")
+        );
+        assert_eq!(
+            Some(&DebugValue::Counter(5)),
+            metrics.get("cohere_output_tokens_total")
+        );
+        assert!(matches!(
+            metrics.get("cohere_time_to_first_token_seconds"),
+            Some(DebugValue::Histogram(values)) if values.len() == 1
+        ));
+        assert!(matches!(
+            metrics.get("cohere_request_duration_seconds"),
+            Some(DebugValue::Histogram(values)) if values.len() == 1
+        ));
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_blocking() {
+        // Create mock server
+        let mut mock_server = mockito::Server::new();
+        let mock_url = mock_server.url();
+
+        let mock_chat_stream = [
+            "{\"is_finished\":false,\"event_type\":\"stream-start\",\"generation_id\":\"0c9cb118-f841-4588-b835-f9a4fe2c572e\"}\n",
+            "{\"is_finished\":false,\"event_type\":\"text-generation\",\"text\":\"Thomas P. Frank.\"}\n",
+            "{\"is_finished\":true,\"event_type\":\"stream-end\",\"response\":{\"response_id\":\"feab94ed-789b-42f2-8f4f-c49d56d28734\",\"text\":\"Thomas P. Frank.\",\"generation_id\":\"0c9cb118-f841-4588-b835-f9a4fe2c572e\"},\"finish_reason\":\"COMPLETE\"}\n",
+        ];
+
+        // Create mocks
+        let mock_check_api_key = mock_server
+            .mock("POST", "/check-api-key")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body("{\"valid\":true,\"organization_id\":null,\"owner_id\":null}")
+            .create();
+        let mock_chat = mock_server
+            .mock("POST", "/chat")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_chunked_body(move |w| {
+                for chunk in mock_chat_stream.iter() {
+                    w.write_all(chunk.as_bytes()).unwrap();
+                }
+                Ok(())
+            })
+            .create();
+
+        let client = cohere_rust::blocking::Cohere::new(mock_url, "test-key");
+
+        assert!(client.check_api_key().is_ok());
+
+        let messages: Vec<ChatStreamResponse> = client
+            .chat(&ChatRequest {
+                message: "who wrote the book where is my cheese?".into(),
+                ..Default::default()
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        // assert that mock endpoints were called
+        mock_check_api_key.assert();
+        mock_chat.assert();
+
+        assert_eq!(3, messages.len());
+        assert!(matches!(
+            &messages[1],
+            ChatStreamResponse::ChatTextGeneration { text, .. } if text == "Thomas P. Frank."
+        ));
+    }
+
+    #[cfg(any(
+        feature = "rustls-tls",
+        feature = "rustls-tls-webpki-roots",
+        feature = "rustls-tls-native-roots",
+        feature = "native-tls"
+    ))]
+    #[tokio::test]
+    async fn test_root_certificate() {
+        use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio_rustls::{
+            rustls::{
+                crypto::ring,
+                pki_types::{CertificateDer, PrivatePkcs8KeyDer},
+                ServerConfig,
+            },
+            TlsAcceptor,
+        };
+
+        // An internal CA that issues the certificate of a TLS endpoint
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, "Internal CA");
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let server_key = KeyPair::generate().unwrap();
+        let server_cert = CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .signed_by(&server_key, &ca, &ca_key)
+            .unwrap();
+
+        let config = ServerConfig::builder_with_provider(ring::default_provider().into())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from(server_cert.der().to_vec())],
+                PrivatePkcs8KeyDer::from(server_key.serialize_der()).into(),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                // Handshakes from clients that don't trust the CA fail
+                let Ok(mut stream) = acceptor.accept(stream).await else {
+                    continue;
+                };
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                // Only the headers are read, as the response doesn't depend on the body
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    let read = stream.read(&mut buf).await.unwrap();
+                    assert!(read > 0, "connection closed before the request was read");
+                    request.extend_from_slice(&buf[..read]);
+                }
+                let body = r#"{"valid":true,"organization_id":null,"owner_id":null}"#;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+        let api_url = format!("https://localhost:{port}");
+
+        // The endpoint's certificate isn't trusted without the internal CA
+        let client = Cohere::builder()
+            .api_url(&api_url)
+            .api_key("test-key")
+            .build();
+        assert!(client.check_api_key().await.is_err());
+
+        let client = Cohere::builder()
+            .api_url(&api_url)
+            .api_key("test-key")
+            .add_root_certificate(cohere_rust::Certificate::from_pem(ca.pem().as_bytes()).unwrap())
+            .build();
+        client.check_api_key().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_transport() {
+        let transport = Arc::new(InMemoryTransport::new());
+        transport
+            .push(
+                Endpoint::Embed,
+                InMemoryResponse::json(&serde_json::json!({
+                    "id": "3915734e-bd8e-4ff4-9be1-f6dfd29dc386",
+                    "texts": ["hi"],
+                    "embeddings": [[0.5, -1.25]],
+                })),
+            )
+            .push(
+                Endpoint::Chat,
+                // messages split across chunks are reassembled
+                InMemoryResponse::stream([
+                    "{\"is_finished\":false,\"event_type\":\"stream-start\",\"generation_id\":\"0c9cb118\"}\n{\"is_finished\":false,",
+                    "\"event_type\":\"text-generation\",\"text\":\"Thomas P. Frank.\"}\n",
+                ]),
+            );
+
+        let client = Cohere::builder()
+            .api_key("test-key")
+            .transport(transport.clone())
+            .build();
+
+        let embeddings = client
+            .embed(&EmbedRequest {
+                texts: vec!["hi".to_string()].into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(vec![vec![0.5, -1.25]], embeddings);
+
+        let mut stream = client
+            .chat(&ChatRequest {
+                message: "who wrote the book where is my cheese?".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let mut messages = Vec::new();
+        while let Some(message) = stream.recv().await {
+            messages.push(message.unwrap());
+        }
+        assert_eq!(2, messages.len());
+        assert!(matches!(
+            &messages[1],
+            ChatStreamResponse::ChatTextGeneration { text, .. } if text == "Thomas P. Frank."
+        ));
+
+        // no response has been queued for a second embed request
+        let response = client
+            .embed(&EmbedRequest {
+                texts: vec!["hi".to_string()].into(),
+                ..Default::default()
+            })
+            .await;
+        assert!(matches!(response, Err(CohereApiError::TransportError(_))));
+
+        let requests = transport.requests();
+        assert_eq!(3, requests.len());
+        assert_eq!(Endpoint::Embed, requests[0].endpoint);
+        assert_eq!("https://api.cohere.com/v1/embed", requests[0].url.as_str());
+        assert_eq!("Bearer test-key", requests[0].headers["authorization"]);
+        assert_eq!(
+            serde_json::json!({ "texts": ["hi"], "truncate": "END" }),
+            serde_json::from_slice::<serde_json::Value>(&requests[0].body).unwrap()
+        );
+        assert_eq!(Endpoint::Chat, requests[1].endpoint);
+    }
+
+    #[tokio::test]
+    async fn test_mock_cohere() {
+        // business logic that only depends on the API operations
+        async fn best_document(api: &dyn CohereApi, query: &str) -> Result<usize, CohereApiError> {
+            let results = api
+                .rerank(&ReRankRequest {
+                    query: query.into(),
+                    documents: vec!["cheese".into(), "mouse".into()].into(),
+                    ..Default::default()
+                })
+                .await?;
+            Ok(results[0].index as usize)
+        }
+
+        let mock = MockCohere::new();
+        mock.push_rerank(vec![ReRankResult {
+            index: 1,
+            relevance_score: 0.9,
+            document: None,
+        }])
+        .push_error(
+            Endpoint::Rerank,
+            CohereApiError::ApiError(
+                reqwest::StatusCode::TOO_MANY_REQUESTS,
+                "rate limited".to_string(),
+            ),
+        )
+        .push_chat(vec![ChatStreamResponse::ChatTextGeneration {
+            is_finished: false,
+            text: "Thomas P. Frank.".to_string(),
+        }]);
+
+        assert_eq!(
+            1,
+            best_document(&mock, "who moved my cheese?").await.unwrap()
+        );
+        assert!(matches!(
+            best_document(&mock, "who moved my cheese?").await,
+            Err(CohereApiError::ApiError(status, _)) if status == 429
+        ));
+
+        let mut stream = mock
+            .chat(&ChatRequest {
+                message: "who wrote the book where is my cheese?".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(matches!(
+            stream.recv().await,
+            Some(Ok(ChatStreamResponse::ChatTextGeneration { text, .. })) if text == "Thomas P. Frank."
+        ));
+        assert!(stream.recv().await.is_none());
+
+        let calls = mock.calls();
+        assert_eq!(3, calls.len());
+        assert!(
+            matches!(&calls[0], MockCall::Rerank(request) if request.query == "who moved my cheese?")
+        );
+        assert!(
+            matches!(&calls[2], MockCall::Chat(request) if request.message == "who wrote the book where is my cheese?")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chat_session() {
+        fn reply(text: &str, tool_calls: Vec<ToolCall>) -> Vec<ChatStreamResponse> {
+            vec![
+                ChatStreamResponse::ChatTextGeneration {
+                    is_finished: false,
+                    text: text.to_string(),
+                },
+                ChatStreamResponse::ChatStreamEnd {
+                    finish_reason: FinishReason::Complete,
+                    is_finished: true,
+                    response: ChatResponse {
+                        generation_id: "0c9cb118-f841-4588-b835-f9a4fe2c572e".to_string(),
+                        response_id: "feab94ed-789b-42f2-8f4f-c49d56d28734".to_string(),
+                        text: text.to_string(),
+                        prompt: None,
+                        tool_calls,
+                    },
+                },
+            ]
+        }
+
+        let weather = ToolCall {
+            name: "weather".to_string(),
+            parameters: json!({ "city": "Toronto" }).as_object().unwrap().clone(),
+        };
+        let mock = MockCohere::new();
+        mock.push_chat(reply("Hello!", vec![]))
+            .push_error(Endpoint::Chat, CohereApiError::QueueFull)
+            .push_chat(reply("", vec![weather.clone()]))
+            .push_chat(reply("It is sunny in Toronto.", vec![]));
+
+        let mut session = ChatSession::new(ChatRequest {
+            preamble: Some("Be brief".to_string()),
+            tools: Some(
+                vec![Tool {
+                    name: "weather".to_string(),
+                    description: "Looks up the weather in a city".to_string(),
+                    parameter_definitions: Default::default(),
+                }]
+                .into(),
+            ),
+            ..Default::default()
+        });
+
+        // non-streaming turn
+        let response = session.send(&mock, "Hi").await.unwrap();
+        assert_eq!("Hello!", response.text);
+
+        // a failed turn isn't added to the history
+        assert!(session.send(&mock, "What's the weather?").await.is_err());
+        assert_eq!(2, session.history.len());
+
+        let response = session.send(&mock, "What's the weather?").await.unwrap();
+        assert_eq!(vec![weather.clone()], response.tool_calls);
+        assert_eq!(response.tool_calls, session.tool_calls());
+
+        // streaming turn answering the tool call
+        let results = vec![ToolResult {
+            call: weather.clone(),
+            outputs: vec![json!({ "forecast": "sunny" })],
+        }];
+        let mut stream = session
+            .send_tool_results_stream(&mock, results.clone())
+            .await
+            .unwrap();
+        let mut text = String::new();
+        while let Some(message) = stream.recv().await {
+            if let ChatStreamResponse::ChatTextGeneration { text: chunk, .. } = message.unwrap() {
+                text.push_str(&chunk);
+            }
+        }
+        assert_eq!("It is sunny in Toronto.", text);
+        assert!(session.tool_calls().is_empty());
+
+        assert_eq!(
+            vec![
+                ChatMessage::user("Hi"),
+                ChatMessage::chatbot("Hello!"),
+                ChatMessage::user("What's the weather?"),
+                ChatMessage::Chatbot {
+                    message: "".to_string(),
+                    tool_calls: vec![weather],
+                },
+                ChatMessage::Tool {
+                    tool_results: results.clone(),
+                },
+                ChatMessage::chatbot("It is sunny in Toronto."),
+            ],
+            session.history
+        );
+        // replies without tool calls are read from histories saved before tool calls were supported
+        assert_eq!(
+            ChatMessage::chatbot("Hello!"),
+            serde_json::from_value(json!({ "role": "CHATBOT", "message": "Hello!" })).unwrap()
+        );
+
+        // every turn is sent with the settings and the history before it
+        let calls = mock.calls();
+        assert_eq!(4, calls.len());
+        let MockCall::Chat(first) = &calls[0] else {
+            panic!("unexpected call {:?}", calls[0]);
+        };
+        assert!(first.chat_history.is_none());
+        assert_eq!(Some("Be brief"), first.preamble.as_deref());
+        assert_eq!(1, first.tools.as_ref().unwrap().len());
+        let MockCall::Chat(last) = &calls[3] else {
+            panic!("unexpected call {:?}", calls[3]);
+        };
+        assert_eq!("", last.message);
+        assert_eq!(4, last.chat_history.as_ref().unwrap().len());
+        assert_eq!(results.as_slice(), last.tool_results.as_deref().unwrap());
+
+        // tool calls are also streamed as they are generated
+        let message: ChatStreamResponse = serde_json::from_str(
+            r#"{"is_finished":false,"event_type":"tool-calls-generation","text":"I will look up the weather.","tool_calls":[{"name":"weather","parameters":{"city":"Toronto"}}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            message,
+            ChatStreamResponse::ChatToolCallsGeneration { tool_calls, .. } if tool_calls == [results[0].call.clone()]
+        ));
+
+        // the session can be saved and resumed
+        let saved = serde_json::to_string(&session).unwrap();
+        assert!(saved.contains(r#""role":"TOOL""#));
+        let resumed: ChatSession = serde_json::from_str(&saved).unwrap();
+        assert_eq!(session.history, resumed.history);
+        assert_eq!(session.settings.preamble, resumed.settings.preamble);
+    }
+
+    #[tokio::test]
+    async fn test_tool_agent() {
+        fn reply(text: &str, tool_calls: Vec<ToolCall>) -> Vec<ChatStreamResponse> {
+            vec![
+                ChatStreamResponse::ChatTextGeneration {
+                    is_finished: false,
+                    text: text.to_string(),
+                },
+                ChatStreamResponse::ChatStreamEnd {
+                    finish_reason: FinishReason::Complete,
+                    is_finished: true,
+                    response: ChatResponse {
+                        generation_id: "0c9cb118-f841-4588-b835-f9a4fe2c572e".to_string(),
+                        response_id: "feab94ed-789b-42f2-8f4f-c49d56d28734".to_string(),
+                        text: text.to_string(),
+                        prompt: None,
+                        tool_calls,
+                    },
+                },
+            ]
+        }
+        fn call(name: &str, parameters: serde_json::Value) -> ToolCall {
+            ToolCall {
+                name: name.to_string(),
+                parameters: parameters.as_object().unwrap().clone(),
+            }
+        }
+        fn definition(name: &str) -> Tool {
+            Tool {
+                name: name.to_string(),
+                description: format!("The {name} tool"),
+                parameter_definitions: Default::default(),
+            }
+        }
+
+        struct Failing;
+
+        #[async_trait]
+        impl AgentTool for Failing {
+            fn definition(&self) -> Tool {
+                definition("failing")
+            }
+
+            async fn call(
+                &self,
+                _parameters: serde_json::Map<String, serde_json::Value>,
+            ) -> Result<Vec<serde_json::Value>, ToolError> {
+                Err("out of order".into())
+            }
+        }
+
+        // both calls to `add` must be running at the same time to get past the barrier
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let add = FnTool::new(definition("add"), move |parameters| {
+            let barrier = barrier.clone();
+            async move {
+                barrier.wait().await;
+                let sum = parameters["a"].as_i64().unwrap() + parameters["b"].as_i64().unwrap();
+                Ok::<_, ToolError>(vec![json!({ "sum": sum })])
+            }
+        });
+        let mut agent = ToolAgent::new(ChatRequest {
+            preamble: Some("Be brief".to_string()),
+            ..Default::default()
+        })
+        .tool(add)
+        .tool(Failing)
+        .max_steps(2);
+
+        let calls = vec![
+            call("add", json!({ "a": 1, "b": 2 })),
+            call("add", json!({ "a": 3, "b": 4 })),
+            call("failing", json!({})),
+            call("missing", json!({})),
+        ];
+        let mock = MockCohere::new();
+        mock.push_chat(reply("I will add the numbers.", calls.clone()))
+            .push_chat(reply("The sums are 3 and 7.", vec![]));
+
+        let mut text = String::new();
+        let mut logged = Vec::new();
+        let run = tokio::time::timeout(
+            Duration::from_secs(5),
+            agent.run_with(&mock, "What are 1 + 2 and 3 + 4?", |event| match event {
+                AgentEvent::Message(ChatStreamResponse::ChatTextGeneration {
+                    text: chunk, ..
+                }) => text.push_str(chunk),
+                AgentEvent::Message(_) => {}
+                AgentEvent::Step(step) => logged.push(step.clone()),
+            }),
+        )
+        .await
+        .expect("the tools should be called concurrently")
+        .unwrap();
+
+        assert_eq!("I will add the numbers.The sums are 3 and 7.", text);
+        assert_eq!("The sums are 3 and 7.", run.response.text);
+        assert_eq!(logged, run.steps);
+        let results = vec![
+            ToolResult {
+                call: calls[0].clone(),
+                outputs: vec![json!({ "sum": 3 })],
+            },
+            ToolResult {
+                call: calls[1].clone(),
+                outputs: vec![json!({ "sum": 7 })],
+            },
+            ToolResult {
+                call: calls[2].clone(),
+                outputs: vec![json!({ "error": "out of order" })],
+            },
+            ToolResult {
+                call: calls[3].clone(),
+                outputs: vec![json!({ "error": "there is no tool named `missing`" })],
+            },
+        ];
+        assert_eq!(
+            vec![AgentStep {
+                text: "I will add the numbers.".to_string(),
+                tool_calls: calls.clone(),
+                tool_results: results.clone(),
+            }],
+            run.steps
+        );
+        assert_eq!(4, agent.session.history.len());
+
+        let mock_calls = mock.calls();
+        let MockCall::Chat(first) = &mock_calls[0] else {
+            panic!("unexpected call {:?}", mock_calls[0]);
+        };
+        assert_eq!(
+            Some(&[definition("add"), definition("failing")][..]),
+            first.tools.as_deref()
+        );
+        let MockCall::Chat(second) = &mock_calls[1] else {
+            panic!("unexpected call {:?}", mock_calls[1]);
+        };
+        assert_eq!(Some(results.as_slice()), second.tool_results.as_deref());
+
+        // a run that doesn't finish within the step limit is removed from the history
+        let calls = vec![call("failing", json!({}))];
+        mock.push_chat(reply("", calls.clone()))
+            .push_chat(reply("", calls.clone()))
+            .push_chat(reply("", calls));
+        let error = agent.run(&mock, "Try again").await.unwrap_err();
+        let AgentError::StepLimitReached(steps) = &error else {
+            panic!("unexpected error {error:?}");
+        };
+        assert_eq!(2, steps.len());
+        assert_eq!(
+            "The model was still calling tools after 2 steps",
+            error.to_string()
+        );
+        assert_eq!(4, agent.session.history.len());
+    }
+
+    #[cfg(feature = "derive")]
+    #[tokio::test]
+    async fn test_derive_cohere_tool() {
+        use std::collections::BTreeMap;
+
+        use cohere_rust::{
+            agent::{CohereTool, TypedTool},
+            api::chat::ToolParameter,
+        };
+
+        /// Looks up the weather
+        /// in a city.
+        #[derive(CohereTool, Debug, PartialEq)]
+        struct WeatherLookup {
+            /// The name of the city.
+            city: String,
+            /// The number of days to forecast.
+            days: Option<u8>,
+            #[cohere_tool(rename = "units")]
+            unit_names: Vec<String>,
+        }
+
+        /// Tells the time.
+        #[derive(CohereTool)]
+        #[cohere_tool(name = "time")]
+        struct CurrentTime {}
+
+        let parameter =
+            |description: Option<&str>, parameter_type: &str, required: bool| ToolParameter {
+                description: description.map(str::to_string),
+                parameter_type: parameter_type.to_string(),
+                required,
+            };
+        assert_eq!(
+            Tool {
+                name: "weather_lookup".to_string(),
+                description: "Looks up the weather in a city.".to_string(),
+                parameter_definitions: BTreeMap::from([
+                    (
+                        "city".to_string(),
+                        parameter(Some("The name of the city."), "str", true)
+                    ),
+                    (
+                        "days".to_string(),
+                        parameter(Some("The number of days to forecast."), "int", false)
+                    ),
+                    ("units".to_string(), parameter(None, "List[str]", true)),
+                ]),
+            },
+            WeatherLookup::definition()
+        );
+        assert_eq!(
+            Tool {
+                name: "time".to_string(),
+                description: "Tells the time.".to_string(),
+                parameter_definitions: BTreeMap::new(),
+            },
+            CurrentTime::definition()
+        );
+
+        // calls are parsed into the parameter struct
+        let parameters = |parameters: serde_json::Value| parameters.as_object().unwrap().clone();
+        assert_eq!(
+            WeatherLookup {
+                city: "Toronto".to_string(),
+                days: None,
+                unit_names: vec!["celsius".to_string()],
+            },
+            WeatherLookup::from_parameters(parameters(
+                json!({ "city": "Toronto", "units": ["celsius"] })
+            ))
+            .unwrap()
+        );
+        assert_eq!(
+            "Missing required parameter `city`",
+            WeatherLookup::from_parameters(parameters(json!({ "units": [] })))
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "Invalid value for parameter `days`: invalid type: string \"two\", expected u8",
+            WeatherLookup::from_parameters(parameters(
+                json!({ "city": "Toronto", "days": "two", "units": [] })
+            ))
+            .unwrap_err()
+            .to_string()
+        );
+
+        // an agent calls typed tools with the parsed parameters
+        let weather = TypedTool::new(|lookup: WeatherLookup| async move {
+            Ok::<_, ToolError>(vec![json!({ "city": lookup.city, "days": lookup.days })])
+        });
+        let mut agent = ToolAgent::new(ChatRequest::default()).tool(weather);
+        assert_eq!(
+            Some(&[WeatherLookup::definition()][..]),
+            agent.session.settings.tools.as_deref()
+        );
+
+        let calls = vec![
+            ToolCall {
+                name: "weather_lookup".to_string(),
+                parameters: parameters(json!({ "city": "Toronto", "days": 2, "units": [] })),
+            },
+            ToolCall {
+                name: "weather_lookup".to_string(),
+                parameters: parameters(json!({ "days": 2 })),
+            },
+        ];
+        let end = |tool_calls: Vec<ToolCall>| ChatStreamResponse::ChatStreamEnd {
+            finish_reason: FinishReason::Complete,
+            is_finished: true,
+            response: ChatResponse {
+                generation_id: "0c9cb118-f841-4588-b835-f9a4fe2c572e".to_string(),
+                response_id: "feab94ed-789b-42f2-8f4f-c49d56d28734".to_string(),
+                text: String::new(),
+                prompt: None,
+                tool_calls,
+            },
+        };
+        let mock = MockCohere::new();
+        mock.push_chat(vec![end(calls)])
+            .push_chat(vec![end(vec![])]);
+        let run = agent.run(&mock, "What's the weather?").await.unwrap();
+
+        let outputs: Vec<_> = run.steps[0]
+            .tool_results
+            .iter()
+            .map(|result| result.outputs.clone())
+            .collect();
+        assert_eq!(
+            vec![
+                vec![json!({ "city": "Toronto", "days": 2 })],
+                vec![json!({ "error": "Missing required parameter `city`" })],
+            ],
+            outputs
+        );
+    }
+
+    #[tokio::test]
+    async fn test_history_truncation() {
+        let history = vec![
+            ChatMessage::System {
+                message: "always rhyme".to_string(),
+            },
+            ChatMessage::User {
+                message: "a b c".to_string(),
+            },
+            ChatMessage::Chatbot {
+                message: "d e".to_string(),
+                tool_calls: vec![],
+            },
+            ChatMessage::User {
+                message: "f".to_string(),
+            },
+            ChatMessage::Chatbot {
+                message: "g".to_string(),
+                tool_calls: vec![],
+            },
+        ];
+        let settings = ChatRequest {
+            preamble: Some("be brief".to_string()),
+            max_input_tokens: Some(12),
+            ..Default::default()
+        };
+
+        // the oldest turn is dropped, counting tokens with the tokenize endpoint
+        let server = FakeServer::start().await.unwrap();
+        let client = Cohere::new(server.url(), "test-key");
+        let mut session = ChatSession::new(settings.clone());
+        session.history = history.clone();
+        session.truncation = Some(HistoryTruncation::new(TruncationStrategy::DropOldest));
+        session.send(&client, "h i").await.unwrap();
+
+        let chat = server
+            .requests()
+            .into_iter()
+            .find(|request| request.endpoint == Endpoint::Chat)
+            .unwrap();
+        assert_eq!(
+            json!([
+                { "role": "SYSTEM", "message": "always rhyme" },
+                { "role": "USER", "message": "f" },
+                { "role": "CHATBOT", "message": "g" },
+            ]),
+            chat.body["chat_history"]
+        );
+        assert_eq!(5, session.history.len());
+
+        // token counts are cached, so only the previous reply is counted on the next turn
+        let tokenized = |server: &FakeServer| {
+            server
+                .requests()
+                .iter()
+                .filter(|request| request.endpoint == Endpoint::Tokenize)
+                .count()
+        };
+        let before = tokenized(&server);
+        session.send(&client, "h i").await.unwrap();
+        assert_eq!(1, tokenized(&server) - before);
+
+        // older turns are summarized, counting tokens with a custom counter
+        struct Words;
+
+        #[async_trait]
+        impl TokenCounter for Words {
+            async fn count_tokens(
+                &self,
+                text: &str,
+                _model: Option<&GenerateModel>,
+            ) -> Result<usize, CohereApiError> {
+                Ok(text.split_whitespace().count())
+            }
+        }
+
+        let mock = MockCohere::new();
+        mock.push_chat(vec![ChatStreamResponse::ChatTextGeneration {
+            is_finished: false,
+            text: "rhymes".to_string(),
+        }])
+        .push_chat(vec![]);
+        let mut session = ChatSession::new(settings.clone());
+        session.history = history.clone();
+        session.truncation = Some(
+            HistoryTruncation::new(TruncationStrategy::Summarize { keep_turns: 1 })
+                .token_counter(Words),
+        );
+        assert!(session.send(&mock, "h i").await.is_err());
+        // the reply ended before the stream end, so the summary isn't kept
+        assert_eq!(history, session.history);
+
+        let calls = mock.calls();
+        let MockCall::Chat(summary) = &calls[0] else {
+            panic!("unexpected call {:?}", calls[0]);
+        };
+        assert!(summary
+            .message
+            .ends_with("System: always rhyme\nUser: a b c\nChatbot: d e\n"));
+        let MockCall::Chat(request) = &calls[1] else {
+            panic!("unexpected call {:?}", calls[1]);
+        };
+        assert_eq!(
+            Some(
+                &[
+                    ChatMessage::System {
+                        message: "Summary of the earlier conversation: rhymes".to_string()
+                    },
+                    ChatMessage::User {
+                        message: "f".to_string()
+                    },
+                    ChatMessage::Chatbot {
+                        message: "g".to_string(),
+                        tool_calls: vec![],
+                    },
+                ][..]
+            ),
+            request.chat_history.as_deref()
+        );
+
+        // a failed chat request leaves the history as it was before truncating
+        let mock = MockCohere::new();
+        mock.push_error(Endpoint::Chat, CohereApiError::QueueFull);
+        let mut session = ChatSession::new(settings);
+        session.history = history.clone();
+        session.truncation =
+            Some(HistoryTruncation::new(TruncationStrategy::DropOldest).token_counter(Words));
+        assert!(session.send(&mock, "h i").await.is_err());
+        assert_eq!(history, session.history);
+
+        // the system messages are kept by default when a session is loaded without the setting
+        let truncation: HistoryTruncation =
+            serde_json::from_value(json!({ "strategy": "DropOldest" })).unwrap();
+        assert!(truncation.keep_system_messages);
+
+        assert_eq!(Some(128_000), GenerateModel::CommandRPlus.context_length());
+        assert_eq!(
+            None,
+            GenerateModel::Custom("my-model".to_string()).context_length()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cassette() {
+        // Create mock server
+        let mut mock_server = mockito::Server::new_async().await;
+        let mock_url = mock_server.url();
+
+        let mock_chat_stream = [
+            "{\"is_finished\":false,\"event_type\":\"stream-start\",\"generation_id\":\"0c9cb118-f841-4588-b835-f9a4fe2c572e\"}\n",
+            "{\"is_finished\":false,\"event_type\":\"text-generation\",\"text\":\"Thomas P. Frank.\"}\n",
+        ];
+
+        // Create mocks
+        let mock_tokenize = mock_server
+            .mock("POST", "/tokenize")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_header("vary", "Origin")
+            .with_header("vary", "Accept-Encoding")
+            .with_body(r#"{"tokens":[33555,1114],"token_strings":["token","ize"]}"#)
+            .create_async()
+            .await;
+        let mock_chat = mock_server
+            .mock("POST", "/chat")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_chunked_body(move |w| {
+                for chunk in mock_chat_stream.iter() {
+                    w.write_all(chunk.as_bytes()).unwrap();
+                    w.flush().unwrap();
+                }
+                Ok(())
+            })
+            .create_async()
+            .await;
+
+        let tokenize_request = TokenizeRequest {
+            text: "tokenize".into(),
+            model: None,
+        };
+        let chat_request = ChatRequest {
+            message: "who wrote the book where is my cheese?".into(),
+            ..Default::default()
+        };
+
+        async fn run(
+            client: &Cohere,
+            tokenize_request: &TokenizeRequest<'_>,
+            chat_request: &ChatRequest<'_>,
+        ) -> (Vec<u64>, Vec<ChatStreamResponse>) {
+            let tokens = client.tokenize(tokenize_request).await.unwrap().tokens;
+            let mut stream = client.chat(chat_request).await.unwrap();
+            let mut messages = Vec::new();
+            while let Some(message) = stream.recv().await {
+                messages.push(message.unwrap());
+            }
+            (tokens, messages)
+        }
+
+        // record the traffic to the API
+        let recorder = Arc::new(RecordingTransport::default());
+        let client = Cohere::builder()
+            .api_url(mock_url)
+            .api_key("test-key")
+            .transport(recorder.clone())
+            .build();
+        let recorded = run(&client, &tokenize_request, &chat_request).await;
+
+        // assert that mock endpoints were called
+        mock_tokenize.assert_async().await;
+        mock_chat.assert_async().await;
+
+        let path =
+            std::env::temp_dir().join(format!("cohere-cassette-{}.json", std::process::id()));
+        recorder.save(&path).unwrap();
+        let cassette = std::fs::read_to_string(&path).unwrap();
+        assert!(!cassette.contains("test-key"));
+        assert!(cassette.contains("REDACTED"));
+
+        // every value of a repeated header is recorded
+        let tokenize = &recorder.cassette().interactions[0];
+        assert_eq!(
+            Some(&vec!["Origin".to_string(), "Accept-Encoding".to_string()]),
+            tokenize.response.headers.get("vary")
+        );
+
+        let chat = &recorder.cassette().interactions[1];
+        assert_eq!(Endpoint::Chat, chat.request.endpoint);
+        assert_eq!(true, chat.request.body["stream"]);
+        assert_eq!(mock_chat_stream.concat(), chat.response.chunks.concat());
+
+        // replay the recorded traffic without the API, recording the replay to compare it with the original
+        let replay = Arc::new(RecordingTransport::new(
+            ReplayTransport::load(&path).unwrap(),
+        ));
+        let client = Cohere::builder()
+            .api_key("another-key")
+            .transport(replay.clone())
+            .build();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            recorded,
+            run(&client, &tokenize_request, &chat_request).await
+        );
+        assert_eq!(
+            recorder.cassette().interactions[0].response,
+            replay.cassette().interactions[0].response
+        );
+        // every recorded interaction has been replayed
+        assert!(matches!(
+            client.tokenize(&tokenize_request).await,
+            Err(CohereApiError::TransportError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fake_server() {
+        // Start the fake server
+        let server = FakeServer::start().await.unwrap();
+        let client = Cohere::new(server.url(), "test-key");
+
+        let tokens = client
+            .tokenize(&TokenizeRequest {
+                text: "Hello fake world".into(),
+                model: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(vec!["hello", "fake", "world"], tokens.token_strings);
+        let text = client
+            .detokenize(&DetokenizeRequest {
+                tokens: tokens.tokens.into(),
+                model: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!("hello fake world", text);
+
+        let request = EmbedRequest {
+            texts: vec!["hi".to_string(), "there".to_string()].into(),
+            ..Default::default()
+        };
+        let embeddings = client.embed(&request).await.unwrap();
+        assert_eq!(2, embeddings.len());
+        assert_eq!(FAKE_EMBEDDING_DIMENSIONS, embeddings[0].len());
+        assert_ne!(embeddings[0], embeddings[1]);
+        assert_eq!(embeddings, client.embed(&request).await.unwrap());
+
+        let results = client
+            .rerank(&ReRankRequest {
+                query: "capital of France".into(),
+                documents: vec![
+                    "Berlin is in Germany".into(),
+                    "Paris is the capital of France".into(),
+                    "The capital of Spain".into(),
+                ]
+                .into(),
+                top_n: Some(2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![1, 2],
+            results.iter().map(|r| r.index).collect::<Vec<_>>()
+        );
+        assert_eq!(1.0, results[0].relevance_score);
+
+        let classifications = client
+            .classify(&ClassifyRequest {
+                inputs: vec!["send me money".to_string()].into(),
+                examples: vec![
+                    ClassifyExample {
+                        text: "confirm your email".into(),
+                        label: "ham".into(),
+                    },
+                    ClassifyExample {
+                        text: "send money now".into(),
+                        label: "spam".into(),
+                    },
+                ]
+                .into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!("spam", classifications[0].prediction);
+
+        // chat is streamed one word at a time, delayed by the configured latency
+        server.set_latency(Duration::from_millis(50));
+        let start = Instant::now();
+        let mut stream = client
+            .chat(&ChatRequest {
+                message: "hi there".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        server.set_latency(Duration::ZERO);
+
+        let mut text = String::new();
+        while let Some(message) = stream.recv().await {
+            match message.unwrap() {
+                ChatStreamResponse::ChatTextGeneration { text: chunk, .. } => text.push_str(&chunk),
+                ChatStreamResponse::ChatStreamEnd {
+                    finish_reason,
+                    response,
+                    ..
+                } => {
+                    assert_eq!(FinishReason::Complete, finish_reason);
+                    assert_eq!("You said: hi there", response.text);
+                }
+                ChatStreamResponse::ChatStreamStart { .. } => {}
+                message => panic!("unexpected message {message:?}"),
+            }
+        }
+        assert_eq!("You said: hi there", text);
+
+        // injected failures are returned in order
+        server.fail_next(
+            Endpoint::Tokenize,
+            FakeFailure::RateLimited(Some(Duration::from_secs(1))),
+        );
+        server.fail_next(Endpoint::Tokenize, FakeFailure::ServerError);
+        let request = TokenizeRequest {
+            text: "hi".into(),
+            model: None,
+        };
+        for status in [429, 500] {
+            match client.tokenize(&request).await {
+                Err(CohereApiError::ApiError(code, _)) => assert_eq!(status, code.as_u16()),
+                response => panic!("unexpected response {response:?}"),
+            }
+        }
+        assert!(client.tokenize(&request).await.is_ok());
+
+        server.fail_next(Endpoint::Chat, FakeFailure::MalformedStream);
+        let mut stream = client
+            .chat(&ChatRequest {
+                message: "hi".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(matches!(
+            stream.recv().await,
+            Some(Ok(ChatStreamResponse::ChatStreamStart { .. }))
+        ));
+        assert!(stream.recv().await.unwrap().is_err());
+        assert!(stream.recv().await.is_none());
+
+        // a connection closed mid-stream ends the stream with the read error
+        server.fail_next(Endpoint::Chat, FakeFailure::Disconnect);
+        let mut stream = client
+            .chat(&ChatRequest {
+                message: "hi".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(matches!(
+            stream.recv().await,
+            Some(Ok(ChatStreamResponse::ChatStreamStart { .. }))
+        ));
+        assert!(matches!(
+            stream.recv().await,
+            Some(Err(CohereStreamError::ReadError(_)))
+        ));
+        assert!(stream.recv().await.is_none());
+
+        // every request was recorded with its headers
+        let requests = server.requests();
+        assert_eq!(12, requests.len());
+        assert_eq!(Endpoint::Chat, requests[11].endpoint);
+        assert_eq!("Bearer test-key", requests[0].headers["authorization"]);
+    }
+
+    #[tokio::test]
+    async fn test_rerank_items() {
+        #[derive(serde::Serialize, Debug, PartialEq)]
+        struct Product {
+            title: &'static str,
+            description: &'static str,
+            brand: &'static str,
+        }
+
+        let products = vec![
+            Product {
+                title: "Trail running shoes",
+                description: "Light shoes with a grippy sole",
+                brand: "Acme",
+            },
+            Product {
+                title: "Rain jacket",
+                description: "A waterproof jacket for running in the rain",
+                brand: "Acme",
+            },
+            Product {
+                title: "Running socks",
+                description: "Cushioned socks",
+                brand: "Running Co",
+            },
+        ];
+
+        let server = FakeServer::start().await.unwrap();
+        let client = Cohere::new(server.url(), "test-key");
+        let options = ReRankOptions {
+            rank_fields: Some(vec!["title".to_string(), "description".to_string()]),
+            top_n: Some(2),
+            ..ReRankOptions::new("waterproof running jacket")
+        };
+
+        // items are returned from most to least relevant
+        let ranked = client.rerank_items(&options, products).await.unwrap();
+        assert_eq!(2, ranked.len());
+        assert_eq!("Rain jacket", ranked[0].item.title);
+        assert_eq!(1.0, ranked[0].relevance_score);
+        assert_eq!("Trail running shoes", ranked[1].item.title);
+
+        // items are sent as objects with the rank fields
+        let body = &server.requests()[0].body;
+        assert_eq!(json!(["title", "description"]), body["rank_fields"]);
+        assert_eq!("Acme", body["documents"][0]["brand"]);
+
+        // documents are returned in the results when requested
+        let results = client
+            .rerank(&ReRankRequest {
+                query: "capital of France".into(),
+                documents: vec![
+                    "Berlin is in Germany".into(),
+                    json!({ "text": "Paris is the capital of France", "id": "paris" })
+                        .as_object()
+                        .unwrap()
+                        .clone()
+                        .into(),
+                ]
+                .into(),
+                top_n: Some(1),
+                return_documents: Some(true),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            json!({ "text": "Paris is the capital of France", "id": "paris" }).as_object(),
+            results[0].document.as_ref()
+        );
+
+        // items must serialize to strings or objects
+        let error = client.rerank_items(&options, vec![1, 2]).await.unwrap_err();
+        assert!(matches!(error, CohereApiError::JsonError(_)));
+        assert_eq!(
+            ReRankDocument::Text("plain text".to_string()),
+            ReRankDocument::from_serialize("plain text").unwrap()
+        );
+
+        // results that don't match an item are reported rather than dropped
+        let result = |index| ReRankResult {
+            index,
+            relevance_score: 0.5,
+            document: None,
+        };
+        let mock = MockCohere::new();
+        mock.push_rerank(vec![result(0), result(3)])
+            .push_rerank(vec![result(1), result(1)]);
+        for _ in 0..2 {
+            let error = mock
+                .rerank_items(&options, vec!["a", "b", "c"])
+                .await
+                .unwrap_err();
+            assert!(matches!(error, CohereApiError::TransportError(_)));
+        }
+    }
+
+    #[cfg(feature = "local-tokenizer")]
+    #[tokio::test]
+    async fn test_local_tokenizer() {
+        use cohere_rust::tokenizer::{LocalTokenizer, TokenizerError};
+
+        // A word-level tokenizer is small enough to define inline
+        let definition = json!({
+            "version": "1.0",
+            "truncation": null,
+            "padding": null,
+            "added_tokens": [],
+            "normalizer": null,
+            "pre_tokenizer": { "type": "Whitespace" },
+            "post_processor": null,
+            "decoder": null,
+            "model": {
+                "type": "WordLevel",
+                "vocab": { "[UNK]": 0, "hello": 1, "world": 2 },
+                "unk_token": "[UNK]"
+            }
+        })
+        .to_string();
+
+        // The tokenizer is looked up, downloaded once and then loaded from the cache
+        let mut mock_server = mockito::Server::new_async().await;
+        let url = format!("{}/tokenizers/test.json", mock_server.url());
+        let mock_model = mock_server
+            .mock("GET", "/models/command-r")
+            .match_header("authorization", "Bearer test-key")
+            .with_status(200)
+            .with_body(json!({ "name": "command-r", "tokenizer_url": url }).to_string())
+            .create_async()
+            .await;
+        let mock_missing = mock_server
+            .mock("GET", "/models/custom-model")
+            .with_status(404)
+            .with_body(r#"{"message":"model not found"}"#)
+            .create_async()
+            .await;
+        let mock_endpoint = mock_server
+            .mock("GET", "/tokenizers/test.json")
+            .with_status(200)
+            .with_body(&definition)
+            .expect(1)
+            .create_async()
+            .await;
+        let cache_dir =
+            std::env::temp_dir().join(format!("cohere-tokenizers-{}", std::process::id()));
+        let client = reqwest::Client::new();
+
+        LocalTokenizer::from_url_cached_in(&client, &url, &cache_dir)
+            .await
+            .unwrap();
+        let tokenizer = LocalTokenizer::from_url_cached_in(&client, &url, &cache_dir)
+            .await
+            .unwrap();
+        mock_endpoint.assert_async().await;
+        // URLs that only differ in characters that aren't kept in file names are cached separately
+        let cached: Vec<_> = std::fs::read_dir(&cache_dir).unwrap().collect();
+        assert_eq!(1, cached.len());
+        assert!(LocalTokenizer::from_url_cached_in(
+            &client,
+            &format!("{}/tokenizers_test.json", mock_server.url()),
+            &cache_dir
+        )
+        .await
+        .is_err());
+
+        // The tokenizer of a model is looked up with the models API, and is already cached
+        let co = Cohere::new(mock_server.url(), "test-key");
+        let model_tokenizer = co
+            .local_tokenizer_cached_in(&GenerateModel::CommandR, &cache_dir)
+            .await
+            .unwrap();
+        mock_model.assert_async().await;
+        mock_endpoint.assert_async().await;
+        assert_eq!(
+            tokenizer.tokenize("hello").unwrap().tokens,
+            model_tokenizer.tokenize("hello").unwrap().tokens
+        );
+        match co
+            .local_tokenizer_cached_in(
+                &GenerateModel::Custom("custom-model".to_string()),
+                &cache_dir,
+            )
+            .await
+        {
+            Err(TokenizerError::Model(CohereApiError::ApiError(status, message))) => {
+                assert_eq!(404, status.as_u16());
+                assert_eq!("model not found", message);
+            }
+            response => panic!("unexpected response {response:?}"),
+        }
+        mock_missing.assert_async().await;
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+
+        let response = tokenizer.tokenize("hello world").unwrap();
+        assert_eq!(vec![1, 2], response.tokens);
+        assert_eq!(vec!["hello", "world"], response.token_strings);
+        assert_eq!(
+            "hello world [UNK]",
+            tokenizer.detokenize(&[1, 2, 0]).unwrap()
+        );
+        assert!(tokenizer.detokenize(&[u64::MAX]).is_err());
+
+        assert_eq!(3, tokenizer.count_tokens("hello big world").unwrap());
+        assert_eq!(
+            vec![1, 0, 2],
+            tokenizer
+                .count_tokens_batch(&["hello", "", "hello world"])
+                .unwrap()
+        );
+        assert_eq!(
+            2,
+            TokenCounter::count_tokens(&tokenizer, "world hello", None)
+                .await
+                .unwrap()
+        );
+
+        // Invalid tokenizers are rejected
+        assert!(LocalTokenizer::from_bytes("{}").is_err());
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_cli() {
+        use std::{
+            io::Write,
+            process::{Command, Stdio},
+        };
+
+        // Start the fake server
+        let server = FakeServer::start().await.unwrap();
+        let url = server.url();
+
+        // The CLI runs in its own home directory, so that it can't touch the user's files
+        let home = std::env::temp_dir().join(format!("cohere-cli-{}", std::process::id()));
+        std::fs::create_dir_all(&home).unwrap();
+
+        // The CLI blocks until it exits, so run it off the server's runtime
+        let cli = {
+            let home = home.clone();
+            move |args: &'static [&'static str], stdin: &'static str| {
+                let url = url.clone();
+                let home = home.clone();
+                tokio::task::spawn_blocking(move || {
+                    let mut child = Command::new(env!("CARGO_BIN_EXE_cohere"))
+                        .args(args)
+                        .env("COHERE_API_KEY", "test-key")
+                        .env("COHERE_API_URL", url)
+                        .env("HOME", home)
+                        .stdin(Stdio::piped())
+                        .stdout(Stdio::piped())
+                        .stderr(Stdio::piped())
+                        .spawn()
+                        .unwrap();
+                    child
+                        .stdin
+                        .take()
+                        .unwrap()
+                        .write_all(stdin.as_bytes())
+                        .unwrap();
+                    child.wait_with_output().unwrap()
+                })
+            }
+        };
+
+        let output = cli(&["tokenize", "Hello world"], "").await.unwrap();
+        assert!(output.status.success());
+        let output = String::from_utf8(output.stdout).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(2, lines.len());
+        assert!(lines[0].ends_with("\thello"));
+
+        let output = cli(&["embed", "--format", "npy"], "hi\n\nthere\n")
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        assert!(output.stdout.starts_with(b"\x93NUMPY\x01\x00"));
+        // two rows of little-endian f64 after a header padded to 128 bytes
+        assert_eq!(128 + 2 * FAKE_EMBEDDING_DIMENSIONS * 8, output.stdout.len());
+
+        let output = cli(&["embed", "--format", "csv"], "say \"hi\"\n")
+            .await
+            .unwrap();
+        let output = String::from_utf8(output.stdout).unwrap();
+        assert!(output.starts_with("\"say \"\"hi\"\"\","));
+        assert_eq!(
+            FAKE_EMBEDDING_DIMENSIONS,
+            output.trim().split(',').count() - 1
+        );
+
+        let output = cli(
+            &["rerank", "--query", "capital city", "-n", "1"],
+            "a river\nthe capital city\n",
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            "1.0000\t1\tthe capital city\n",
+            String::from_utf8(output.stdout).unwrap()
+        );
+
+        // the conversation is sent with every message of a session
+        let output = cli(&["chat"], "hello\n/reset\nhi\nagain\n").await.unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            "You said: hello\nYou said: hi\nYou said: again\n",
+            String::from_utf8(output.stdout).unwrap()
+        );
+        let chats = server
+            .requests()
+            .into_iter()
+            .filter(|request| request.endpoint == Endpoint::Chat)
+            .collect::<Vec<_>>();
+        assert_eq!(3, chats.len());
+        assert!(chats[1].body.get("chat_history").is_none());
+        assert_eq!(2, chats[2].body["chat_history"].as_array().unwrap().len());
+        // piped input isn't saved to the history
+        assert!(!home.join(".cohere_history").exists());
+
+        server.fail_next(Endpoint::CheckApiKey, FakeFailure::ServerError);
+        let output = cli(&["check-key"], "").await.unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("error: "));
+
+        std::fs::remove_dir_all(home).unwrap();
+    }
+
     #[tokio::test]
     async fn test_api_failure() {
         // Create mock server
@@ -615,7 +3222,7 @@ mod tests {
         let client = Cohere::new(mock_url, "test-key");
 
         let request = TokenizeRequest {
-            text: "",
+            text: "".into(),
             model: None,
         };
 
@@ -650,7 +3257,7 @@ mod tests {
 
         let response = client
             .chat(&ChatRequest {
-                message: "who wrote the book where is my cheese?",
+                message: "who wrote the book where is my cheese?".into(),
                 ..Default::default()
             })
             .await;